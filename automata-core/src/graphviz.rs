@@ -0,0 +1,216 @@
+//! GraphViz output shared by every machine in the course
+//!
+//! All of the binaries draw their machines the same way: a point-shaped
+//! `start` node pointing at the start state, double circles for accept
+//! states, and one labeled edge per pair of connected nodes.
+//!
+//! ```
+//! use automata_core::graphviz::{self, Diagram, Edge};
+//!
+//! let diagram = Diagram::new("q1", vec!["q2".to_string()]);
+//! let edges = vec![Edge::labeled("q1", "q2", "a")];
+//! assert!(graphviz::generate(&diagram, edges).contains("    q1 -> q2 [label=\"a\"];\n"));
+//! ```
+
+use std::fmt::Display;
+use std::io::Write;
+
+/// The nodes of a machine to draw, apart from its edges
+/// Edges are passed separately so they can be produced lazily.
+#[derive(Clone, Debug)]
+pub struct Diagram {
+    pub start: String,
+    pub accept: Vec<String>,
+    /// Display names for nodes that shouldn't just show their id
+    pub names: Vec<(String, String)>,
+    /// Nodes drawn dashed (and drawn even if they have no edges)
+    pub dashed: Vec<String>,
+    /// Written between the labels of edges that get merged, `,` by default
+    pub separator: &'static str
+}
+
+/// A single arrow between two nodes
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub label: Option<String>,
+    pub dashed: bool
+}
+
+impl Diagram {
+    /// Create a diagram with no named or dashed nodes.
+    pub fn new<N: Display>(start: N, accept: Vec<String>) -> Diagram {
+        Diagram {start: start.to_string(), accept, names: vec![], dashed: vec![], separator: ","}
+    }
+}
+
+impl Edge {
+    /// Create an unlabeled edge.
+    pub fn new<N: Display>(from: N, to: N) -> Edge {
+        Edge {from: from.to_string(), to: to.to_string(), label: None, dashed: false}
+    }
+
+    /// Create an edge with a label over it.
+    pub fn labeled<N: Display, L: Display>(from: N, to: N, label: L) -> Edge {
+        Edge {label: Some(label.to_string()), ..Edge::new(from, to)}
+    }
+
+    /// Draw this edge dashed, e.g. for a spontaneous transition.
+    pub fn dashed(self) -> Edge {
+        Edge {dashed: true, ..self}
+    }
+}
+
+/// Get the GraphViz representation of a machine
+pub fn generate<I: IntoIterator<Item=Edge>>(diagram: &Diagram, edges: I) -> String {
+    let mut buffer = vec![];
+    write(&mut buffer, diagram, edges).unwrap();
+    String::from_utf8(buffer).unwrap()
+}
+
+/// Write the GraphViz representation of a machine.
+/// Edges between the same two nodes are merged into one edge whose label
+/// lists each of theirs, e.g. `a,b,c`. Edges leaving the same node should
+/// be next to each other; they're written as soon as the next node starts,
+/// so a huge machine never has to be held in memory as one big string.
+pub fn write<W: Write, I: IntoIterator<Item=Edge>>(w: &mut W, diagram: &Diagram, edges: I) -> std::io::Result<()> {
+    writeln!(w, "digraph {{\n    rankdir=LR;")?;
+    write_body(w, "", "    ", diagram, edges)?;
+    write!(w, "}}")
+}
+
+/// Wrap a machine in a labeled cluster so several can share one graph
+/// Node ids are prefixed with the cluster index to keep them distinct.
+pub fn cluster<I: IntoIterator<Item=Edge>>(index: usize, label: &str, diagram: &Diagram, edges: I) -> String {
+    let mut buffer = vec![];
+    writeln!(buffer, "    subgraph cluster_{} {{\n        label=\"{}\";", index, escape(label)).unwrap();
+    write_body(&mut buffer, &format!("c{}_", index), "        ", diagram, edges).unwrap();
+    writeln!(buffer, "    }}").unwrap();
+    String::from_utf8(buffer).unwrap()
+}
+
+/// Combine several clusters into one graph
+pub fn generate_clusters(clusters: &[String]) -> String {
+    format!("digraph {{\n    rankdir=LR;\n{}}}", clusters.concat())
+}
+
+/// Write the node and edge statements for a single machine
+fn write_body<W: Write, I: IntoIterator<Item=Edge>>(w: &mut W, prefix: &str, indent: &str, diagram: &Diagram, edges: I) -> std::io::Result<()> {
+    writeln!(w, "{}node [shape=point]; {}start;", indent, prefix)?;
+    write!(w, "{}node [shape=doublecircle];", indent)?;
+    for node in &diagram.accept {
+        write!(w, " {}{};", prefix, node)?;
+    }
+    writeln!(w, "\n{}node [shape=circle];", indent)?;
+
+    for (node, name) in &diagram.names {
+        writeln!(w, "{}{}{} [label=\"{}\"];", indent, prefix, node, escape(name))?;
+    }
+    for node in &diagram.dashed {
+        writeln!(w, "{}{}{} [style=dashed];", indent, prefix, node)?;
+    }
+
+    writeln!(w, "{}{p}start -> {p}{};", indent, diagram.start, p=prefix)?;
+
+    // Edges leaving the current node, merged by target (labels are escaped
+    // first so the separator can be a GraphViz escape like `\n`)
+    let mut pending: Vec<(Edge, Vec<String>)> = vec![];
+    for edge in edges {
+        if pending.first().is_some_and(|(first, _)| first.from != edge.from) {
+            write_edges(w, prefix, indent, diagram.separator, pending.drain(..))?;
+        }
+        let label = edge.label.as_deref().map(escape);
+        match pending.iter_mut().find(|(other, _)| other.to == edge.to && other.dashed == edge.dashed) {
+            Some((_, labels)) => labels.extend(label),
+            None => pending.push((edge, label.into_iter().collect()))
+        }
+    }
+    write_edges(w, prefix, indent, diagram.separator, pending.drain(..))
+}
+
+/// Write merged edges, given each edge and its escaped labels
+fn write_edges<W: Write, I: Iterator<Item=(Edge, Vec<String>)>>(w: &mut W, prefix: &str, indent: &str, separator: &str, edges: I) -> std::io::Result<()> {
+    for (edge, labels) in edges {
+        let attrs = Some(labels)
+                .filter(|labels| !labels.is_empty())
+                .map(|labels| format!("label=\"{}\"", labels.join(separator)))
+                .into_iter()
+                .chain(if edge.dashed { Some("style=dashed".to_string()) } else { None })
+                .collect::<Vec<String>>();
+        if attrs.is_empty() {
+            writeln!(w, "{}{p}{} -> {p}{};", indent, edge.from, edge.to, p=prefix)?;
+        } else {
+            writeln!(w, "{}{p}{} -> {p}{} [{}];", indent, edge.from, edge.to, attrs.join(", "), p=prefix)?;
+        }
+    }
+    Ok(())
+}
+
+/// Escape a label so it can sit inside `"..."`
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[test]
+fn test_generate() {
+    let mut diagram = Diagram::new("q1", vec!["q2".to_string(), "q3".to_string()]);
+    diagram.names.push(("q1".to_string(), "say \"hi\"".to_string()));
+    diagram.dashed.push("q4".to_string());
+    let edges = vec![
+        Edge::labeled("q1", "q2", "a"),
+        Edge::new("q2", "q3"),
+        Edge::labeled("q3", "q1", "&epsilon;").dashed()
+    ];
+
+    assert_eq!(generate(&diagram, edges),
+"digraph {
+    rankdir=LR;
+    node [shape=point]; start;
+    node [shape=doublecircle]; q2; q3;
+    node [shape=circle];
+    q1 [label=\"say \\\"hi\\\"\"];
+    q4 [style=dashed];
+    start -> q1;
+    q1 -> q2 [label=\"a\"];
+    q2 -> q3;
+    q3 -> q1 [label=\"&epsilon;\", style=dashed];
+}");
+}
+
+#[test]
+fn test_merge_edges() {
+    let diagram = Diagram::new("q0", vec![]);
+    let edges = vec![
+        Edge::labeled("q0", "q1", "a"),
+        Edge::labeled("q0", "q2", "b"),
+        Edge::labeled("q0", "q1", "c"),
+        Edge::labeled("q0", "q1", "&epsilon;").dashed(),
+        Edge::labeled("q1", "q1", "a"),
+        Edge::labeled("q1", "q1", "\"")
+    ];
+    assert!(generate(&diagram, edges).ends_with(
+"    start -> q0;
+    q0 -> q1 [label=\"a,c\"];
+    q0 -> q2 [label=\"b\"];
+    q0 -> q1 [label=\"&epsilon;\", style=dashed];
+    q1 -> q1 [label=\"a,\\\"\"];
+}"));
+
+    let diagram = Diagram {separator: "\\n", ..diagram};
+    let edges = vec![Edge::labeled("q0", "q1", "a, b"), Edge::labeled("q0", "q1", "c")];
+    assert!(generate(&diagram, edges).contains("    q0 -> q1 [label=\"a, b\\nc\"];\n"));
+}
+
+#[test]
+fn test_clusters() {
+    let diagram = Diagram::new(0, vec![]);
+    let graph = generate_clusters(&[
+        cluster(0, "before", &diagram, vec![Edge::labeled(0, 0, "a")]),
+        cluster(1, "after", &diagram, vec![])
+    ]);
+
+    assert!(graph.starts_with("digraph {\n    rankdir=LR;\n    subgraph cluster_0 {\n        label=\"before\";\n"));
+    assert!(graph.contains("        c0_start -> c0_0;\n        c0_0 -> c0_0 [label=\"a\"];\n    }\n"));
+    assert!(graph.ends_with("        c1_start -> c1_0;\n    }\n}"));
+}
@@ -0,0 +1,1068 @@
+//! CSIS-616 - Shared automata library
+//! 
+//! The YAML-backed DFA used by hw2 (drawing it as a graph) and hw3
+//! (testing strings against it), plus the GraphViz builder that every
+//! binary uses to draw its machines.
+//! 
+//! # Example
+//! 
+//! ```
+//! let mut dfa: automata_core::DFA = serde_yaml::from_str("
+//! alphabet: [a, b]
+//! start: 1
+//! accept: [2]
+//! transitions: [[2, 1], [2, 1]]
+//! ").unwrap();
+//! dfa.n_states = 2;
+//! 
+//! assert!(dfa.validate().is_ok());
+//! assert!(dfa.run("ba", false));
+//! ```
+
+#![allow(clippy::upper_case_acronyms)]
+
+use std::convert::TryFrom;
+use std::io::Write;
+use serde::{Deserialize, Serialize};
+
+pub mod graphviz;
+pub mod nfa;
+
+/// # Deterministic Finite Automaton Structure
+/// 
+/// Create a structure that the YAML files will be deserialized into.
+/// Each row of `transitions` either lists destinations in alphabet order
+/// (`[2, 1]`) or maps symbols to destinations (`{a: 2, b: 1}`).
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "DFARecord")]
+pub struct DFA {
+    pub alphabet: Vec<String>,
+    pub start: u32,
+    pub accept: Vec<u32>,
+    pub transitions: Vec<Vec<u32>>,
+
+    // Optional label for each state, in order; states are just
+    // numbered if this is left out
+    pub names: Vec<String>,
+    
+    // This field isn't loaded from the YAML file; it's counted
+    // from the transition rows
+    pub n_states: u32,
+
+    // Declared symbols that no symbol-keyed row uses (these are left out
+    // of `alphabet`), and the (state, symbol) pairs of keyed transitions
+    // on symbols that were never declared (these are ignored)
+    pub unused_symbols: Vec<String>,
+    pub undeclared_symbols: Vec<(u32, String)>
+}
+
+/// A DFA as written in the YAML file, before keyed rows are put in
+/// alphabet order
+#[derive(Deserialize)]
+struct DFARecord {
+    #[serde(deserialize_with = "deserialize_alphabet")]
+    alphabet: Vec<String>,
+    start: u32,
+    accept: Vec<u32>,
+    transitions: Vec<Row>,
+    #[serde(default)]
+    names: Vec<String>
+}
+
+/// One state's transitions, either positional or keyed by symbol
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Row {
+    Positional(Vec<u32>),
+    Keyed(std::collections::BTreeMap<String, u32>)
+}
+
+impl TryFrom<DFARecord> for DFA {
+    type Error = String;
+
+    fn try_from(record: DFARecord) -> Result<DFA, String> {
+        // A symbol is only unused if every row is keyed and none mentions it
+        let keyed_only = !record.transitions.is_empty()
+                && record.transitions.iter().all(|row| matches!(row, Row::Keyed(_)));
+        let (alphabet, unused_symbols): (Vec<String>, Vec<String>) = record.alphabet.iter().cloned()
+                .partition(|symbol| !keyed_only || record.transitions.iter().any(|row| match row {
+                    Row::Keyed(map) => map.contains_key(symbol),
+                    Row::Positional(_) => true
+                }));
+
+        let mut undeclared_symbols = vec![];
+        let mut transitions = vec![];
+        for (state, row) in (1..).zip(record.transitions) {
+            match row {
+                Row::Positional(row) => transitions.push(row),
+                Row::Keyed(map) => {
+                    let row = alphabet.iter()
+                            .map(|symbol| map.get(symbol).copied()
+                                    .ok_or_else(|| format!("State `{}` has no transition on `{}`", state, symbol)))
+                            .collect::<Result<Vec<u32>, String>>()?;
+                    undeclared_symbols.extend(map.into_keys()
+                            .filter(|symbol| !alphabet.contains(symbol) && !unused_symbols.contains(symbol))
+                            .map(|symbol| (state, symbol)));
+                    transitions.push(row);
+                }
+            }
+        }
+
+        Ok(DFA {
+            alphabet,
+            start: record.start,
+            accept: record.accept,
+            n_states: transitions.len() as u32,
+            transitions,
+            names: record.names,
+            unused_symbols,
+            undeclared_symbols
+        })
+    }
+}
+
+/// # Graph Structure
+/// 
+/// Contains a vector of nodes and the index of the start node.
+#[derive(Debug)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub start_node: u32
+}
+
+/// # Node Structure
+/// 
+/// Contains a vector of all connected node indices, the transition
+/// labels for those connections (can be left undefined), a
+/// boolean indicating whether this is a final node, and the name
+/// to display for it (if it has one).
+#[derive(Debug)]
+pub struct Node {
+    pub connections: Vec<u32>,
+    pub labels: Vec<Option<String>>,
+    pub accept_state: bool,
+    pub name: Option<String>
+}
+
+/// A graph's states and transitions, for tools that consume JSON
+#[derive(Serialize, Deserialize)]
+struct GraphRecord {
+    start: u32,
+    accept: Vec<u32>,
+    transitions: Vec<TransitionRecord>
+}
+
+#[derive(Serialize, Deserialize)]
+struct TransitionRecord {
+    from: u32,
+    symbol: String,
+    to: u32
+}
+
+/// Read the alphabet either as a list of symbols (`[a, b, c]` or
+/// `[id, num]`) or as a single string of one-character symbols (`"abc"`)
+fn deserialize_alphabet<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+        where D: serde::Deserializer<'de> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Alphabet {
+        List(Vec<String>),
+        Word(String)
+    }
+
+    Ok(match Alphabet::deserialize(deserializer)? {
+        Alphabet::List(symbols) => symbols,
+        Alphabet::Word(word) => word.chars().map(String::from).collect()
+    })
+}
+
+/// # Streaming Deserialization
+///
+/// Reads a DFA like the derived `Deserialize` does, but checks each row of
+/// transitions as soon as it's read, so a bad row near the top of a huge
+/// file fails right away instead of after the whole DFA has been built, and
+/// the error gives the row's line in the file. serde_yaml still parses the
+/// whole document before any of it is read, so this saves time but not
+/// memory. (Rows can only be checked against the alphabet if it comes first
+/// in the file, and destinations can only be checked against the number of
+/// states once every row has been read, so `validate` should still run
+/// afterwards.)
+struct StreamedDFA(DFA);
+
+impl<'de> Deserialize<'de> for StreamedDFA {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where D: serde::Deserializer<'de> {
+        deserializer.deserialize_map(StreamedDFAVisitor)
+    }
+}
+
+struct StreamedDFAVisitor;
+
+impl<'de> serde::de::Visitor<'de> for StreamedDFAVisitor {
+    type Value = StreamedDFA;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a DFA definition")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where A: serde::de::MapAccess<'de> {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(transparent)]
+        struct Alphabet(#[serde(deserialize_with = "deserialize_alphabet")] Vec<String>);
+
+        let mut alphabet: Option<Vec<String>> = None;
+        let mut start = None;
+        let mut accept = None;
+        let mut transitions = None;
+        let mut names = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "alphabet" => alphabet = Some(map.next_value::<Alphabet>()?.0),
+                "start" => start = Some(map.next_value()?),
+                "accept" => accept = Some(map.next_value()?),
+                "names" => names = Some(map.next_value()?),
+                "deterministic" => if !map.next_value::<bool>()? {
+                    return Err(A::Error::custom("NFA definitions can't be streamed"));
+                },
+                "epsilon" => return Err(A::Error::custom("Epsilon transitions need `deterministic: false`")),
+                "transitions" => {
+                    let row_len = alphabet.as_ref().map(Vec::len);
+                    transitions = Some(map.next_value_seed(TransitionRows {row_len})?);
+                },
+                _ => { map.next_value::<serde::de::IgnoredAny>()?; }
+            }
+        }
+
+        let record = DFARecord {
+            alphabet: alphabet.ok_or_else(|| A::Error::missing_field("alphabet"))?,
+            start: start.ok_or_else(|| A::Error::missing_field("start"))?,
+            accept: accept.ok_or_else(|| A::Error::missing_field("accept"))?,
+            transitions: transitions.ok_or_else(|| A::Error::missing_field("transitions"))?,
+            names: names.unwrap_or_default()
+        };
+        DFA::try_from(record).map(StreamedDFA).map_err(A::Error::custom)
+    }
+}
+
+/// Reads the transition table one row at a time, checking each positional
+/// row's length (if the alphabet is known) and that no row names state 0.
+struct TransitionRows {
+    row_len: Option<usize>
+}
+
+impl<'de> serde::de::DeserializeSeed<'de> for TransitionRows {
+    type Value = Vec<Row>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where D: serde::Deserializer<'de> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for TransitionRows {
+    type Value = Vec<Row>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a list of transition rows")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where A: serde::de::SeqAccess<'de> {
+        let mut rows = vec![];
+        while let Some(row) = seq.next_element_seed(RowSeed {state: rows.len() + 1, row_len: self.row_len})? {
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+}
+
+/// Reads a single transition row. The checks happen inside the row's own
+/// visitor, so serde_yaml marks their errors with the row's line.
+struct RowSeed {
+    state: usize,
+    row_len: Option<usize>
+}
+
+impl RowSeed {
+    fn unknown_zero<E: serde::de::Error>(&self) -> E {
+        E::custom(format!("State `{}` cannot transition to unknown state `0`", self.state))
+    }
+}
+
+impl<'de> serde::de::DeserializeSeed<'de> for RowSeed {
+    type Value = Row;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where D: serde::Deserializer<'de> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for RowSeed {
+    type Value = Row;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a list or map of transitions")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where A: serde::de::SeqAccess<'de> {
+        use serde::de::Error;
+
+        let mut dests = vec![];
+        while let Some(dest) = seq.next_element::<u32>()? {
+            dests.push(dest);
+        }
+        match self.row_len {
+            Some(row_len) if dests.len() != row_len => {
+                return Err(A::Error::custom(format!("State `{}` defines {} transitions (should define {})",
+                        self.state, dests.len(), row_len)));
+            },
+            _ => ()
+        }
+        if dests.contains(&0) {
+            return Err(self.unknown_zero());
+        }
+        Ok(Row::Positional(dests))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where A: serde::de::MapAccess<'de> {
+        let mut dests = std::collections::BTreeMap::new();
+        while let Some((symbol, dest)) = map.next_entry::<String, u32>()? {
+            dests.insert(symbol, dest);
+        }
+        if dests.values().any(|&dest| dest == 0) {
+            return Err(self.unknown_zero());
+        }
+        Ok(Row::Keyed(dests))
+    }
+}
+
+impl DFA {
+    /// Load the .yaml file specified into a DFA structure
+    /// on the heap and return a point to it via a Box.
+    pub fn new_from_file(filename: &str) -> Result<Box<DFA>, String> {
+        let file = std::fs::File::open(filename)
+                .map_err(|e| format!("Unable to open input: {}", e))?;
+        DFA::from_reader(file)
+    }
+
+    /// Deserialize a DFA from YAML. If it's marked `deterministic: false`,
+    /// it's read as an NFA instead and converted (see `nfa::NFA`).
+    pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Box<DFA>, String> {
+        // Deserialize using serde
+        let yaml: serde_yaml::Value = serde_yaml::from_reader(reader)
+                .map_err(|e| format!("Unable to parse yaml: {}", e))?;
+        if yaml.get("deterministic") == Some(&serde_yaml::Value::Bool(false)) {
+            let nfa: nfa::NFA = serde_yaml::from_value(yaml)
+                    .map_err(|e| format!("Unable to parse yaml: {}", e))?;
+            nfa.validate()?;
+            return Ok(Box::new(nfa.to_dfa()));
+        }
+        if yaml.get("epsilon").is_some() {
+            return Err("Epsilon transitions need `deterministic: false`".to_string());
+        }
+        let mut dfa: DFA = serde_yaml::from_value(yaml)
+                .map_err(|e| format!("Unable to parse yaml: {}", e))?;
+
+        // Compute number of states
+        dfa.n_states = dfa.transitions.len() as u32;
+
+        Ok(Box::new(dfa))
+    }
+
+    /// Load the .yaml file specified like `new_from_file`, but check the
+    /// transition table while reading it (see `StreamedDFA`).
+    pub fn new_from_file_streaming(filename: &str) -> Result<Box<DFA>, String> {
+        let file = std::fs::File::open(filename)
+                .map_err(|e| format!("Unable to open input: {}", e))?;
+        DFA::from_reader_streaming(std::io::BufReader::new(file))
+    }
+
+    /// Deserialize a DFA from YAML, failing on the first bad transition row.
+    pub fn from_reader_streaming<R: std::io::Read>(reader: R) -> Result<Box<DFA>, String> {
+        let StreamedDFA(dfa) = serde_yaml::from_reader(reader)
+                .map_err(|e| format!("Unable to parse yaml: {}", e))?;
+        Ok(Box::new(dfa))
+    }
+
+    /// Check whether this DFA is well-formed.
+    pub fn validate(&self) -> Result<(), String> {
+        let alphabet_len = self.alphabet.len();
+        let out_of_range = |s| !(1..=self.n_states).contains(s);
+
+        // Check start state
+        if out_of_range(&self.start) {
+            return Err(format!("Unknown start state `{}`", self.start));
+        }
+
+        // Check final states
+        for final_state in &self.accept {
+            if out_of_range(final_state) {
+                return Err(format!("Unknown final state `{}`", final_state));
+            }
+        }
+
+        // Check state names
+        if !self.names.is_empty() && self.names.len() != self.n_states as usize {
+            return Err(format!("{} state names given for {} states", self.names.len(), self.n_states));
+        }
+
+        // Check transitions
+        for (state, dest_states) in &mut self.transitions.iter().enumerate() {
+            // Check number of transitions
+            if dest_states.len() != alphabet_len {
+                return Err(format!("State `{}` defines {} transitions (should define {})",
+                        state + 1, dest_states.len(), alphabet_len));
+            }
+
+            // Check transition destinations
+            for dest_state in dest_states {
+                if out_of_range(dest_state) {
+                    return Err(format!("State `{}` cannot transition to unknown state `{}`",
+                            state + 1, &dest_state));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find problems that don't make this DFA invalid but are likely
+    /// copy-paste mistakes. Positional transitions are matched to the
+    /// alphabet by position, so a symbol declared twice gets two edges with
+    /// the same label out of every state, and `accepts` only ever follows
+    /// the first. Symbol-keyed rows can also leave a declared symbol unused
+    /// or use a symbol that was never declared.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        for (column, symbol) in self.alphabet.iter().enumerate() {
+            if let Some(first) = self.alphabet[..column].iter().position(|s| s == symbol) {
+                warnings.push(format!("Symbol `{}` is declared in both column {} and column {} of the alphabet",
+                        symbol, first + 1, column + 1));
+            }
+        }
+        for symbol in &self.unused_symbols {
+            warnings.push(format!("Symbol `{}` is declared but no transition uses it", symbol));
+        }
+        for (state, symbol) in &self.undeclared_symbols {
+            warnings.push(format!("State `{}` has a transition on `{}`, which isn't in the alphabet", state, symbol));
+        }
+        warnings
+    }
+
+    /// Build an equivalent DFA with as few states as possible. States start
+    /// out split into accepting and non-accepting, and each group is split
+    /// again whenever its states disagree on which group a letter leads to.
+    /// Once nothing splits, each group becomes one state, numbered in order
+    /// of its lowest original state. State names are dropped, since a merged
+    /// state has more than one.
+    pub fn minimize(&self) -> DFA {
+        // The group each state is in, starting with accept/non-accept
+        let mut groups: Vec<usize> = (1..=self.n_states)
+                .map(|state| self.accept.contains(&state) as usize)
+                .collect();
+        let mut n_groups = 0;
+
+        loop {
+            // Give every distinct (group, destination groups) pair a new group
+            let mut signatures: Vec<(usize, Vec<usize>)> = vec![];
+            let refined: Vec<usize> = self.transitions.iter().zip(&groups)
+                    .map(|(row, &group)| {
+                        let signature = (group, row.iter().map(|&dest| groups[dest as usize - 1]).collect());
+                        signatures.iter().position(|sig| *sig == signature).unwrap_or_else(|| {
+                            signatures.push(signature);
+                            signatures.len() - 1
+                        })
+                    })
+                    .collect();
+
+            groups = refined;
+            if signatures.len() == n_groups {
+                break;
+            }
+            n_groups = signatures.len();
+        }
+
+        // Each group's transitions are those of any state in it
+        let mut transitions = vec![vec![]; n_groups];
+        for (row, &group) in self.transitions.iter().zip(&groups) {
+            if transitions[group].is_empty() {
+                transitions[group] = row.iter().map(|&dest| groups[dest as usize - 1] as u32 + 1).collect();
+            }
+        }
+        let mut accept: Vec<u32> = self.accept.iter()
+                .map(|&state| groups[state as usize - 1] as u32 + 1)
+                .collect();
+        accept.sort_unstable();
+        accept.dedup();
+
+        DFA {
+            alphabet: self.alphabet.clone(),
+            start: groups[self.start as usize - 1] as u32 + 1,
+            accept,
+            transitions,
+            names: vec![],
+            n_states: n_groups as u32,
+            unused_symbols: self.unused_symbols.clone(),
+            undeclared_symbols: self.undeclared_symbols.clone()
+        }
+    }
+
+    /// Check whether this DFA accepts the given string
+    pub fn accepts(&self, input: &str) -> bool {
+        self.run(input, false)
+    }
+
+    /// Run the DFA on the given string, printing each transition taken
+    /// if `verbose` is set.
+    pub fn run(&self, input: &str, verbose: bool) -> bool {
+        self.run_symbols(&self.tokenize(input), verbose)
+    }
+
+    /// Split a string into symbols: one per character if every symbol in
+    /// the alphabet is a single character, otherwise one per
+    /// whitespace-separated word. (Input split some other way can be
+    /// passed to `run_symbols` directly.)
+    pub fn tokenize<'a>(&self, input: &'a str) -> Vec<&'a str> {
+        if self.alphabet.iter().all(|symbol| symbol.chars().count() == 1) {
+            input.char_indices()
+                    .map(|(i, letter)| &input[i..i + letter.len_utf8()])
+                    .collect()
+        } else {
+            input.split_whitespace().collect()
+        }
+    }
+
+    /// Run the DFA on a string that's already been split into symbols,
+    /// printing each transition taken if `verbose` is set.
+    pub fn run_symbols(&self, input: &[&str], verbose: bool) -> bool {
+        let mut state = self.start;
+        for &letter in input {
+            // Get the transition index for this symbol
+            let transition = self.alphabet.iter().position(|ltr| ltr == letter);
+
+            // Follow the transition to the next state
+            let new_state = match transition {
+                None => {
+                    if verbose {
+                        println!("  (symbol '{}' not in alphabet \u{2192} REJECT)", letter);
+                    }
+                    return false;
+                }
+                Some(index) => self.transitions[state as usize - 1][index]
+            };
+
+            // Print the transition and actually update the state
+            if verbose {
+                println!("  \u{3B4}(q{}, {}) \u{2192} q{}", state, letter, new_state);
+            }
+            state = new_state;
+        }
+        self.accept.contains(&state)
+    }
+
+    /// Generate a Graph structure from this DFA.
+    pub fn to_graph(&self) -> Box<Graph> {
+        // Create a vector of "blank" nodes
+        let mut nodes = (0..self.n_states)
+                .map(|_| Node::new(&self.alphabet))
+                .collect::<Vec<Node>>();
+
+        // Insert the start node
+        let mut start = Node::new_empty();
+        start.connections = vec![self.start];
+        start.labels = vec![None];
+        nodes.insert(0, start);
+
+        // Flag the final states
+        for state_num in &self.accept {
+        nodes[*state_num as usize].accept_state = true;
+        }
+
+        // Connect the nodes
+        for (node, state_num) in nodes.iter_mut().skip(1).zip(0..) {
+        node.connections = self.transitions[state_num].clone();
+        }
+
+        // Name the nodes
+        for (node, name) in nodes.iter_mut().skip(1).zip(&self.names) {
+            node.name = Some(name.clone());
+        }
+
+        Box::new(Graph {nodes, start_node: 0})
+    }
+}
+
+impl Graph {
+    /// Print this graph in debug format to stdout.
+    pub fn print(&self) {
+        println!("{:?}", self); // could use {:#?} for pretty printing
+    }
+
+    /// Print this graph in GraphViz format to stdout.
+    pub fn print_graphviz(&self) {
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        self.write_graphviz(&mut stdout)
+                .and_then(|_| writeln!(stdout))
+                .expect("Unable to write to stdout");
+    }
+
+    /// Return this graph as a string in GraphViz format.
+    pub fn to_graphviz(&self) -> String {
+        let mut buffer = vec![];
+        self.write_graphviz(&mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    /// Write this graph in GraphViz format.
+    /// Each transition is written as soon as it's formatted, so a huge
+    /// machine never has to be held in memory as one big string.
+    pub fn write_graphviz<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let node_id = |i: usize| format!("q{}", i);
+        let start = self.nodes[self.start_node as usize].connections[0];
+        let mut diagram = graphviz::Diagram::new(
+            node_id(start as usize),
+            self.nodes.iter()
+                    .enumerate()
+                    .filter(|node| node.1.accept_state)
+                    .map(|node| node_id(node.0))
+                    .collect()
+        );
+        diagram.names = self.nodes.iter()
+                .enumerate()
+                .filter_map(|(i, node)| node.name.clone().map(|name| (node_id(i), name)))
+                .collect();
+
+        // The start node is drawn by the generator, so skip its edge
+        let edges = self.nodes.iter()
+                .enumerate()
+                .filter(|&(i, _)| i != self.start_node as usize)
+                .flat_map(|(i, node)| node.connections.iter().zip(&node.labels)
+                    .map(move |(&target, label)| graphviz::Edge {
+                        label: label.clone(),
+                        ..graphviz::Edge::new(node_id(i), node_id(target as usize))
+                    }));
+        graphviz::write(w, &diagram, edges)
+    }
+
+    /// Return this graph as a JSON object, e.g.
+    /// `{"start":1,"accept":[2],"transitions":[{"from":1,"symbol":"a","to":2}]}`
+    /// The start node itself is left out; `start` is the state it points to.
+    pub fn to_json(&self) -> String {
+        let start = &self.nodes[self.start_node as usize];
+        let record = GraphRecord {
+            start: start.connections[0],
+            accept: (0..self.nodes.len() as u32)
+                    .filter(|&i| self.nodes[i as usize].accept_state)
+                    .collect(),
+            transitions: self.nodes.iter()
+                    .zip(0..)
+                    .filter(|&(_, i)| i != self.start_node)
+                    .flat_map(|(node, from)| node.connections.iter().zip(&node.labels)
+                        .filter_map(move |(&to, label)| label.clone().map(|symbol| TransitionRecord {from, symbol, to})))
+                    .collect()
+        };
+        serde_json::to_string(&record).unwrap()
+    }
+
+    /// Return this graph as a Mermaid state diagram, for docs that can't
+    /// run GraphViz.
+    pub fn to_mermaid(&self) -> String {
+        let mut lines = vec!["stateDiagram-v2".to_string()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            for (target, label) in node.connections.iter().zip(&node.labels) {
+                match label {
+                    _ if i == self.start_node as usize => lines.push(format!("    [*] --> q{}", target)),
+                    Some(lbl) => lines.push(format!("    q{} --> q{}: {}", i, target, lbl)),
+                    None =>      lines.push(format!("    q{} --> q{}", i, target))
+                }
+            }
+            if node.accept_state {
+                lines.push(format!("    q{} --> [*]", i));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+impl Node {
+    /// Create a new node with the specified transition labels.
+    fn new(labels: &[String]) -> Node {
+        let label_refs = labels.iter()
+                .map(|lbl| Some(lbl.clone()))
+                .collect::<Vec<Option<String>>>();
+        Node {
+            connections: vec![],
+            labels: label_refs,
+            accept_state: false,
+            name: None
+        }
+    }
+
+    /// Create a new empty node.
+    fn new_empty() -> Node {
+        Node {
+            connections: vec![],
+            labels: vec![],
+            accept_state: false,
+            name: None
+        }
+    }
+}
+
+#[test]
+fn test_to_graphviz() {
+    let nodes = vec![
+        Node {
+            connections: vec![1],
+            labels: vec![None],
+            accept_state: false,
+            name: None
+        },
+        Node {
+            connections: vec![1, 2],
+            labels: vec![Some("a".to_string()), Some("b".to_string())],
+            accept_state: false,
+            name: None
+        },
+        Node {
+            connections: vec![2],
+            labels: vec![Some("a".to_string())],
+            accept_state: true,
+            name: None
+        }
+    ];
+    let graph = Graph {
+        nodes,
+        start_node: 0
+    };
+    
+    assert_eq!(graph.to_graphviz(),
+"digraph {
+    rankdir=LR;
+    node [shape=point]; start;
+    node [shape=doublecircle]; q2;
+    node [shape=circle];
+    start -> q1;
+    q1 -> q1 [label=\"a\"];
+    q1 -> q2 [label=\"b\"];
+    q2 -> q2 [label=\"a\"];
+}"
+    );
+}
+
+#[test]
+fn test_accept() {
+    // Accepts strings of {a, b} that end with a b
+    let dfa = DFA {
+        alphabet: vec!["a".to_string(), "b".to_string()],
+        start: 1,
+        accept: vec![2],
+        transitions: vec![vec![2, 1], vec![2, 1]],
+        names: vec![],
+        n_states: 2,
+        unused_symbols: vec![],
+        undeclared_symbols: vec![]
+    };
+    // positive inputs
+    assert!(dfa.accepts("a"));
+    assert!(dfa.accepts("baa"));
+    assert!(dfa.accepts("baba"));
+
+    // negative inputs
+    assert!(!dfa.accepts(""));
+    assert!(!dfa.accepts("b"));
+    assert!(!dfa.accepts("ab"));
+    assert!(!dfa.accepts("abab"));
+}
+
+#[test]
+fn test_accept_unknown_char() {
+    let dfa = DFA {
+        alphabet: vec!["a".to_string(), "b".to_string()],
+        start: 1,
+        accept: vec![2],
+        transitions: vec![vec![2, 1], vec![2, 1]],
+        names: vec![],
+        n_states: 2,
+        unused_symbols: vec![],
+        undeclared_symbols: vec![]
+    };
+    // `c` isn't in the alphabet, so reject instead of panicking
+    assert!(!dfa.accepts("c"));
+    assert!(!dfa.accepts("abc"));
+    assert!(!dfa.accepts("ca"));
+}
+
+#[test]
+fn test_warnings() {
+    // `a` is declared twice, so the third column is dead weight
+    let dfa = DFA {
+        alphabet: vec!["a".to_string(), "b".to_string(), "a".to_string()],
+        start: 1,
+        accept: vec![2],
+        transitions: vec![vec![2, 1, 1], vec![2, 1, 1]],
+        names: vec![],
+        n_states: 2,
+        unused_symbols: vec![],
+        undeclared_symbols: vec![]
+    };
+    assert!(dfa.validate().is_ok());
+    assert_eq!(dfa.warnings(),
+            vec!["Symbol `a` is declared in both column 1 and column 3 of the alphabet"]);
+
+    // a clean alphabet produces no warnings
+    let dfa = DFA {
+        alphabet: vec!["a".to_string(), "b".to_string()],
+        start: 1,
+        accept: vec![2],
+        transitions: vec![vec![2, 1], vec![2, 1]],
+        names: vec![],
+        n_states: 2,
+        unused_symbols: vec![],
+        undeclared_symbols: vec![]
+    };
+    assert!(dfa.warnings().is_empty());
+}
+
+#[test]
+fn test_keyed_transitions() {
+    // same DFA as `test_accept`, but keyed by symbol
+    let yaml = "
+alphabet: [a, b]
+start: 1
+accept: [2]
+transitions: [{b: 1, a: 2}, {a: 2, b: 1}]
+";
+    for dfa in [serde_yaml::from_str::<DFA>(yaml).unwrap(), *DFA::from_reader_streaming(yaml.as_bytes()).unwrap()] {
+        assert_eq!(dfa.transitions, vec![vec![2, 1], vec![2, 1]]);
+        assert!(dfa.validate().is_ok());
+        assert!(dfa.warnings().is_empty());
+        assert!(dfa.accepts("baa"));
+    }
+
+    // a keyed row can't leave out a symbol another row uses
+    let yaml = "
+alphabet: [a, b]
+start: 1
+accept: [2]
+transitions: [{a: 2, b: 1}, {a: 2}]
+";
+    assert_eq!(serde_yaml::from_str::<DFA>(yaml).unwrap_err().to_string(), "State `2` has no transition on `b`");
+}
+
+#[test]
+fn test_symbol_mismatch_warnings() {
+    // `c` is declared but never used, and state 2 uses an undeclared `x`
+    let yaml = "
+alphabet: [a, b, c]
+start: 1
+accept: [2]
+transitions: [{a: 2, b: 1}, {a: 2, b: 1, x: 2}]
+";
+    let dfa = DFA::from_reader(yaml.as_bytes()).unwrap();
+    assert_eq!(dfa.alphabet, vec!["a", "b"]);
+    assert!(dfa.validate().is_ok());
+    assert_eq!(dfa.warnings(), vec![
+        "Symbol `c` is declared but no transition uses it",
+        "State `2` has a transition on `x`, which isn't in the alphabet"
+    ]);
+
+    // with positional rows every declared symbol has a column
+    let yaml = "
+alphabet: [a, b, c]
+start: 1
+accept: [2]
+transitions: [[2, 1, 1], {a: 2, b: 1}]
+";
+    assert!(serde_yaml::from_str::<DFA>(yaml).unwrap_err().to_string().contains("no transition on `c`"));
+}
+
+#[test]
+fn test_alphabet_shorthand() {
+    let yaml = "
+alphabet: [a, b]
+start: 1
+accept: [2]
+transitions: [[2, 1], [2, 1]]
+";
+    let mut list: DFA = serde_yaml::from_str(yaml).unwrap();
+    let mut word: DFA = serde_yaml::from_str(&yaml.replace("[a, b]", "ab")).unwrap();
+    list.n_states = 2;
+    word.n_states = 2;
+
+    assert_eq!(word.alphabet, vec!["a", "b"]);
+    assert_eq!(word.transitions, list.transitions);
+    assert_eq!(word.to_graph().to_graphviz(), list.to_graph().to_graphviz());
+    for input in &["", "a", "b", "ab", "ba"] {
+        assert_eq!(word.accepts(input), list.accepts(input));
+    }
+}
+
+#[test]
+fn test_write_graphviz() {
+    let yaml = "
+alphabet: [a, b]
+start: 1
+accept: [2, 3]
+transitions: [[2, 3], [2, 1], [3, 3]]
+";
+    let mut dfa: DFA = serde_yaml::from_str(yaml).unwrap();
+    dfa.n_states = 3;
+    let graph = dfa.to_graph();
+
+    let mut buffer = vec![];
+    graph.write_graphviz(&mut buffer).unwrap();
+    assert_eq!(String::from_utf8(buffer).unwrap(), graph.to_graphviz());
+    assert!(graph.to_graphviz().contains("    node [shape=doublecircle]; q2; q3;\n"));
+    assert!(graph.to_graphviz().ends_with("    q3 -> q3 [label=\"a,b\"];\n}"));
+}
+
+#[test]
+fn test_to_mermaid() {
+    let yaml = "
+alphabet: [a, b]
+start: 1
+accept: [2]
+transitions: [[2, 1], [2, 1]]
+";
+    let mut dfa: DFA = serde_yaml::from_str(yaml).unwrap();
+    dfa.n_states = 2;
+    assert_eq!(dfa.to_graph().to_mermaid(),
+"stateDiagram-v2
+    [*] --> q1
+    q1 --> q2: a
+    q1 --> q1: b
+    q2 --> q2: a
+    q2 --> q1: b
+    q2 --> [*]");
+}
+
+#[test]
+fn test_streaming_bad_row() {
+    // a big table with a short row near the top
+    let mut yaml = "alphabet: [a, b]\nstart: 1\naccept: [1]\ntransitions:\n".to_string();
+    for state in 1..=20000 {
+        let row = if state == 3 { "[1]".to_string() } else { format!("[{}, 1]", state % 20000 + 1) };
+        yaml.push_str(&format!("  - {}\n", row));
+    }
+    let err = DFA::from_reader_streaming(yaml.as_bytes()).unwrap_err();
+    assert!(err.contains("State `3` defines 1 transitions (should define 2)"), "{}", err);
+    assert!(err.ends_with("at line 7 column 5"), "{}", err);
+
+    // once fixed, the whole table loads and validates
+    let yaml = yaml.replace("  - [1]\n", "  - [4, 1]\n");
+    let dfa = DFA::from_reader_streaming(yaml.as_bytes()).unwrap();
+    assert_eq!(dfa.n_states, 20000);
+    assert!(dfa.validate().is_ok());
+
+    let yaml = "alphabet: ab\nstart: 1\naccept: [1]\ntransitions: [[1, 0]]";
+    let err = DFA::from_reader_streaming(yaml.as_bytes()).unwrap_err();
+    assert!(err.contains("State `1` cannot transition to unknown state `0`"), "{}", err);
+
+    let yaml = "alphabet: ab\nstart: 1\naccept: [1]\ntransitions:\n  - {a: 1, b: 1}\n  - {a: 0, b: 1}\n";
+    let err = DFA::from_reader_streaming(yaml.as_bytes()).unwrap_err();
+    assert!(err.contains("State `2` cannot transition to unknown state `0` at line 6"), "{}", err);
+}
+
+#[test]
+fn test_minimize() {
+    // Accepts strings of {a, b} that end with a b; states 3 and 4 are
+    // copies of 1 and 2
+    let yaml = "
+alphabet: [a, b]
+start: 1
+accept: [2, 4]
+transitions: [[3, 2], [1, 4], [1, 4], [3, 2]]
+";
+    let mut dfa: DFA = serde_yaml::from_str(yaml).unwrap();
+    dfa.n_states = 4;
+
+    let minimal = dfa.minimize();
+    assert!(minimal.validate().is_ok());
+    assert_eq!(minimal.n_states, 2);
+    assert_eq!(minimal.start, 1);
+    assert_eq!(minimal.accept, vec![2]);
+    assert_eq!(minimal.transitions, vec![vec![1, 2], vec![1, 2]]);
+    assert!(minimal.to_graph().nodes.len() < dfa.to_graph().nodes.len());
+
+    // already minimal, so nothing changes
+    let again = minimal.minimize();
+    assert_eq!(again.transitions, minimal.transitions);
+}
+
+#[test]
+fn test_names() {
+    let yaml = "
+alphabet: [a, b]
+start: 1
+accept: [2]
+transitions: [[2, 1], [2, 1]]
+names: [even, \"odd \\\"one\\\"\"]
+";
+    let mut dfa: DFA = serde_yaml::from_str(yaml).unwrap();
+    dfa.n_states = 2;
+    assert!(dfa.validate().is_ok());
+    assert_eq!(dfa.to_graph().to_graphviz(),
+"digraph {
+    rankdir=LR;
+    node [shape=point]; start;
+    node [shape=doublecircle]; q2;
+    node [shape=circle];
+    q1 [label=\"even\"];
+    q2 [label=\"odd \\\"one\\\"\"];
+    start -> q1;
+    q1 -> q2 [label=\"a\"];
+    q1 -> q1 [label=\"b\"];
+    q2 -> q2 [label=\"a\"];
+    q2 -> q1 [label=\"b\"];
+}");
+
+    // the streaming loader reads them too
+    assert_eq!(DFA::from_reader_streaming(yaml.as_bytes()).unwrap().names, dfa.names);
+
+    // one name short
+    dfa.names.pop();
+    assert_eq!(dfa.validate(), Err("1 state names given for 2 states".to_string()));
+}
+
+#[test]
+fn test_multi_char_symbols() {
+    // Accepts `id` followed by any number of `+ id` or `+ num`
+    let yaml = "
+alphabet: [id, num, +]
+start: 1
+accept: [2]
+transitions: [[2, 3, 3], [3, 3, 4], [3, 3, 3], [2, 2, 3]]
+";
+    let mut dfa: DFA = serde_yaml::from_str(yaml).unwrap();
+    dfa.n_states = 4;
+    assert!(dfa.validate().is_ok());
+
+    assert!(dfa.accepts("id"));
+    assert!(dfa.accepts("id + num + id"));
+    assert!(!dfa.accepts("num"));
+    assert!(!dfa.accepts("id +"));
+    assert!(!dfa.accepts("i d"));
+    assert!(dfa.run_symbols(&"id,+,num".split(',').collect::<Vec<&str>>(), false));
+
+    assert!(dfa.to_graph().to_graphviz().contains("    q1 -> q2 [label=\"id\"];\n"));
+}
+
+#[test]
+fn test_to_json() {
+    let dfa = DFA::from_reader("alphabet: ab\nstart: 1\naccept: [2]\ntransitions: [[2, 1], [2, 1]]".as_bytes()).unwrap();
+    let graph = dfa.to_graph();
+    assert_eq!(graph.to_json(), concat!(r#"{"start":1,"accept":[2],"transitions":["#,
+            r#"{"from":1,"symbol":"a","to":2},{"from":1,"symbol":"b","to":1},"#,
+            r#"{"from":2,"symbol":"a","to":2},{"from":2,"symbol":"b","to":1}]}"#));
+
+    let record: GraphRecord = serde_json::from_str(&graph.to_json()).unwrap();
+    assert_eq!(record.transitions.len(), 4);
+}
@@ -0,0 +1,222 @@
+//! # Nondeterministic Finite Automata
+//!
+//! An NFA is written in YAML just like a DFA, plus `deterministic: false`,
+//! except that each cell of the transition table is a list of destination
+//! states (possibly empty) instead of a single one. Epsilon transitions
+//! are listed separately as `[from, to]` pairs:
+//!
+//! ```yaml
+//! epsilon: [[1, 2], [2, 4]]
+//! ```
+
+use serde::{Deserialize};
+use crate::DFA;
+
+/// # Nondeterministic Finite Automaton Structure
+///
+/// Create a structure that NFA YAML files will be deserialized into.
+#[derive(Debug, Deserialize)]
+pub struct NFA {
+    #[serde(deserialize_with = "crate::deserialize_alphabet")]
+    pub alphabet: Vec<String>,
+    pub start: u32,
+    pub accept: Vec<u32>,
+    pub transitions: Vec<Vec<Vec<u32>>>,
+    #[serde(default)]
+    pub epsilon: Vec<(u32, u32)>
+}
+
+impl NFA {
+    /// Check whether this NFA is well-formed.
+    pub fn validate(&self) -> Result<(), String> {
+        let n_states = self.transitions.len() as u32;
+        let out_of_range = |s| !(1..=n_states).contains(s);
+
+        // Check start state
+        if out_of_range(&self.start) {
+            return Err(format!("Unknown start state `{}`", self.start));
+        }
+
+        // Check final states
+        for final_state in &self.accept {
+            if out_of_range(final_state) {
+                return Err(format!("Unknown final state `{}`", final_state));
+            }
+        }
+
+        // Check transitions (an empty list just means no transition)
+        for (state, dest_lists) in self.transitions.iter().enumerate() {
+            if dest_lists.len() != self.alphabet.len() {
+                return Err(format!("State `{}` defines {} transitions (should define {})",
+                        state + 1, dest_lists.len(), self.alphabet.len()));
+            }
+            for dest_state in dest_lists.iter().flatten() {
+                if out_of_range(dest_state) {
+                    return Err(format!("State `{}` cannot transition to unknown state `{}`",
+                            state + 1, dest_state));
+                }
+            }
+        }
+
+        // Check epsilon transitions
+        for (from, to) in &self.epsilon {
+            if out_of_range(from) || out_of_range(to) {
+                return Err(format!("Epsilon transition `{} -> {}` uses an unknown state", from, to));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the states each state can reach with a single epsilon
+    /// transition, indexed from 0.
+    fn epsilon_table(&self) -> Vec<Vec<u32>> {
+        let mut table = vec![vec![]; self.transitions.len()];
+        for &(from, to) in &self.epsilon {
+            table[from as usize - 1].push(to);
+        }
+        table
+    }
+
+    /// Get every state reachable from `states` using only epsilon
+    /// transitions (including `states` themselves), sorted.
+    fn epsilon_closure(states: &[u32], epsilon_table: &[Vec<u32>]) -> Vec<u32> {
+        let mut closure = states.to_vec();
+        let mut stack = states.to_vec();
+        while let Some(state) = stack.pop() {
+            for &next in &epsilon_table[state as usize - 1] {
+                if !closure.contains(&next) {
+                    closure.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+        closure.sort_unstable();
+        closure.dedup();
+        closure
+    }
+
+    /// Convert this NFA to an equivalent DFA with the subset construction.
+    /// Each DFA state stands for a set of NFA states, which becomes its
+    /// name (e.g. `{1, 2}`). The empty set is kept as a trap state, so the
+    /// DFA always has a transition on every symbol. Only sets reachable
+    /// from the start are built, numbered in the order they're found.
+    /// Every set is closed under epsilon transitions as it's built.
+    pub fn to_dfa(&self) -> DFA {
+        let epsilon_table = self.epsilon_table();
+        let mut subsets = vec![NFA::epsilon_closure(&[self.start], &epsilon_table)];
+        let mut transitions: Vec<Vec<u32>> = vec![];
+        while transitions.len() < subsets.len() {
+            let subset = subsets[transitions.len()].clone();
+            let mut row = vec![];
+            for symbol in 0..self.alphabet.len() {
+                // Every state some member of the subset can go to
+                let next = subset.iter()
+                        .flat_map(|&state| self.transitions[state as usize - 1][symbol].iter().copied())
+                        .collect::<Vec<u32>>();
+                let next = NFA::epsilon_closure(&next, &epsilon_table);
+
+                let id = match subsets.iter().position(|known| *known == next) {
+                    Some(id) => id,
+                    None => {
+                        subsets.push(next);
+                        subsets.len() - 1
+                    }
+                };
+                row.push(id as u32 + 1);
+            }
+            transitions.push(row);
+        }
+
+        let accept = (1..=subsets.len() as u32)
+                .filter(|&id| subsets[id as usize - 1].iter().any(|state| self.accept.contains(state)))
+                .collect();
+        let names = subsets.iter()
+                .map(|subset| match subset.is_empty() {
+                    true => "\u{2205}".to_string(),
+                    false => format!("{{{}}}", subset.iter().map(u32::to_string).collect::<Vec<String>>().join(", "))
+                })
+                .collect();
+
+        DFA {
+            alphabet: self.alphabet.clone(),
+            start: 1,
+            accept,
+            n_states: transitions.len() as u32,
+            transitions,
+            names,
+            unused_symbols: vec![],
+            undeclared_symbols: vec![]
+        }
+    }
+}
+
+#[test]
+fn test_to_dfa() {
+    // Accepts strings of {a, b} containing `ab`
+    let yaml = "
+deterministic: false
+alphabet: [a, b]
+start: 1
+accept: [3]
+transitions:
+  - [[1, 2], [1]]
+  - [[], [3]]
+  - [[3], [3]]
+";
+    let dfa = DFA::from_reader(yaml.as_bytes()).unwrap();
+    assert!(dfa.validate().is_ok());
+    assert_eq!(dfa.n_states, 4);
+    assert_eq!(dfa.names, vec!["{1}", "{1, 2}", "{1, 3}", "{1, 2, 3}"]);
+    assert_eq!(dfa.accept, vec![3, 4]);
+    for input in &["", "a", "b", "ba", "aa", "bba", "ab", "aab", "bab", "abba", "bbbbab"] {
+        assert_eq!(dfa.run(input, false), input.contains("ab"), "{}", input);
+    }
+
+    // a dead end becomes a trap state
+    let yaml = yaml.replace("[[1, 2], [1]]", "[[2], []]");
+    let dfa = DFA::from_reader(yaml.as_bytes()).unwrap();
+    assert_eq!(dfa.names, vec!["{1}", "{2}", "\u{2205}", "{3}"]);
+    assert!(dfa.run("abab", false));
+    assert!(!dfa.run("bab", false));
+}
+
+#[test]
+fn test_validate() {
+    let yaml = "
+deterministic: false
+alphabet: [a, b]
+start: 1
+accept: [2]
+transitions: [[[1, 2], []], [[], [4]]]
+";
+    let err = DFA::from_reader(yaml.as_bytes()).unwrap_err();
+    assert_eq!(err, "State `2` cannot transition to unknown state `4`");
+
+    let yaml = yaml.replace("[[], [4]]]", "[[], [2]]]\nepsilon: [[2, 3]]");
+    let err = DFA::from_reader(yaml.as_bytes()).unwrap_err();
+    assert_eq!(err, "Epsilon transition `2 -> 3` uses an unknown state");
+
+    let err = DFA::from_reader_streaming(yaml.as_bytes()).unwrap_err();
+    assert!(err.contains("NFA definitions can't be streamed"), "{}", err);
+}
+
+#[test]
+fn test_epsilon() {
+    // a*b*, with an epsilon move from the a loop to the b loop
+    let yaml = "
+deterministic: false
+alphabet: [a, b]
+start: 1
+accept: [2]
+transitions: [[[1], []], [[], [2]]]
+epsilon: [[1, 2]]
+";
+    let dfa = DFA::from_reader(yaml.as_bytes()).unwrap();
+    assert!(dfa.validate().is_ok());
+    assert_eq!(dfa.names, vec!["{1, 2}", "{2}", "\u{2205}"]);
+    assert_eq!(dfa.transitions, vec![vec![1, 2], vec![3, 2], vec![3, 3]]);
+    assert_eq!(dfa.accept, vec![1, 2]);
+    assert!(dfa.run("aabb", false));
+    assert!(!dfa.run("aba", false));
+}
@@ -0,0 +1,331 @@
+//! CSIS-616 - Command Line Parsing
+//! 
+//! A small argument parser shared by the homework binaries, so each one
+//! describes its flags and arguments in one place and gets `--help` and a
+//! usage message that matches its name for free.
+//! 
+//! # Example
+//! 
+//! ```
+//! let command = cli::Command::new("hw2", "Draw a DFA with GraphViz")
+//!         .flag("minimize", "Collapse equivalent states first")
+//!         .option("output", "file", "Write the GraphViz definition to a file")
+//!         .arg("filename", "YAML file containing the DFA");
+//! 
+//! let args = vec!["--minimize", "dfa.yaml"].into_iter().map(String::from);
+//! let matches = command.parse_from(args).unwrap();
+//! assert!(matches.flag("minimize"));
+//! assert_eq!(matches.value("output"), None);
+//! assert_eq!(matches.arg("filename"), Some("dfa.yaml"));
+//! ```
+
+use std::collections::HashMap;
+
+/// # Command Structure
+/// 
+/// Describes a program's command line: `--flags` (which may take a value)
+/// can go anywhere, and the other arguments fill the positional slots in
+/// order. Everything after a bare `--` is positional.
+pub struct Command {
+    name: &'static str,
+    about: &'static str,
+    flags: Vec<Flag>,
+    args: Vec<Arg>
+}
+
+/// A `--name` flag, with the name of its value if it takes one
+struct Flag {
+    name: &'static str,
+    value: Option<&'static str>,
+    choice: bool,     // whether the value must be one of those listed in its name
+    help: &'static str
+}
+
+/// A positional argument
+struct Arg {
+    name: &'static str,
+    kind: ArgKind,
+    help: &'static str
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ArgKind {
+    Required,
+    Optional,
+    Rest      // every remaining argument, joined with spaces
+}
+
+/// Why a command line couldn't be parsed
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    Help,            // `--help` was passed
+    Invalid(String)  // what was wrong with it
+}
+
+/// # Matches Structure
+/// 
+/// The flags and arguments found on a command line.
+#[derive(Debug, Default)]
+pub struct Matches {
+    flags: HashMap<&'static str, Option<String>>,
+    args: HashMap<&'static str, String>
+}
+
+impl Command {
+    /// Describe a program with no flags or arguments yet.
+    pub fn new(name: &'static str, about: &'static str) -> Command {
+        Command {name, about, flags: vec![], args: vec![]}
+    }
+
+    /// Add a `--name` flag that's either present or not.
+    pub fn flag(mut self, name: &'static str, help: &'static str) -> Command {
+        self.flags.push(Flag {name, value: None, choice: false, help});
+        self
+    }
+
+    /// Add a `--name value` flag.
+    pub fn option(mut self, name: &'static str, value: &'static str, help: &'static str) -> Command {
+        self.flags.push(Flag {name, value: Some(value), choice: false, help});
+        self
+    }
+
+    /// Add a `--name value` flag whose value must be one of `choices`,
+    /// which are separated by `|` (e.g. `graphviz|mermaid`).
+    pub fn choice(mut self, name: &'static str, choices: &'static str, help: &'static str) -> Command {
+        self.flags.push(Flag {name, value: Some(choices), choice: true, help});
+        self
+    }
+
+    /// Add a positional argument that must be given.
+    pub fn arg(self, name: &'static str, help: &'static str) -> Command {
+        self.positional(name, ArgKind::Required, help)
+    }
+
+    /// Add a positional argument that can be left out.
+    pub fn optional_arg(self, name: &'static str, help: &'static str) -> Command {
+        self.positional(name, ArgKind::Optional, help)
+    }
+
+    /// Add a final positional argument that soaks up everything left,
+    /// joined with spaces (e.g. a regex that wasn't quoted).
+    pub fn rest(self, name: &'static str, help: &'static str) -> Command {
+        self.positional(name, ArgKind::Rest, help)
+    }
+
+    fn positional(mut self, name: &'static str, kind: ArgKind, help: &'static str) -> Command {
+        self.args.push(Arg {name, kind, help});
+        self
+    }
+
+    /// Get the one-line usage message, e.g.
+    /// `Usage: ./hw3 [--stream] <filename> [strings]`
+    pub fn usage(&self) -> String {
+        let mut usage = format!("Usage: ./{}", self.name);
+        for flag in &self.flags {
+            match flag.value {
+                Some(value) => usage += &format!(" [--{} <{}>]", flag.name, value),
+                None => usage += &format!(" [--{}]", flag.name)
+            }
+        }
+        for arg in &self.args {
+            match arg.kind {
+                ArgKind::Required => usage += &format!(" <{}>", arg.name),
+                ArgKind::Optional => usage += &format!(" [{}]", arg.name),
+                ArgKind::Rest => usage += &format!(" <{}...>", arg.name)
+            }
+        }
+        usage
+    }
+
+    /// Get the full `--help` text: what the program does, its usage, and
+    /// a line for each argument and flag.
+    pub fn help(&self) -> String {
+        let mut lines = vec![self.about.to_string(), String::new(), self.usage(), String::new()];
+        for arg in &self.args {
+            lines.push(format!("    {:<20}{}", arg.name, arg.help));
+        }
+        for flag in &self.flags {
+            let name = match flag.value {
+                Some(value) => format!("--{} <{}>", flag.name, value),
+                None => format!("--{}", flag.name)
+            };
+            lines.push(format!("    {:<20}{}", name, flag.help));
+        }
+        lines.push(format!("    {:<20}{}", "--help", "Print this message"));
+        lines.join("\n")
+    }
+
+    /// Parse the program's own arguments, printing the help text and
+    /// quitting on `--help`, or the problem and usage on a bad command line.
+    pub fn parse(&self) -> Matches {
+        match self.parse_from(std::env::args().skip(1)) {
+            Ok(matches) => matches,
+            Err(ParseError::Help) => {
+                println!("{}", self.help());
+                std::process::exit(0);
+            },
+            Err(ParseError::Invalid(msg)) => {
+                eprintln!("{}\n{}", msg, self.usage());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Parse the given arguments (not including the program name).
+    pub fn parse_from(&self, args: impl IntoIterator<Item = String>) -> Result<Matches, ParseError> {
+        let mut matches = Matches::default();
+        let mut positionals = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                positionals.extend(&mut args);
+            } else if arg == "--help" {
+                return Err(ParseError::Help);
+            } else if let Some(name) = arg.strip_prefix("--") {
+                let flag = self.flags.iter().find(|flag| flag.name == name)
+                        .ok_or_else(|| ParseError::Invalid(format!("Unknown flag `{}`", arg)))?;
+                if matches.flags.contains_key(flag.name) {
+                    return Err(ParseError::Invalid(format!("`{}` was given more than once", arg)));
+                }
+                let value = match flag.value {
+                    Some(value) => Some(args.next().ok_or_else(||
+                            ParseError::Invalid(format!("Missing <{}> after `{}`", value, arg)))?),
+                    None => None
+                };
+                if let (true, Some(choices), Some(value)) = (flag.choice, flag.value, &value) {
+                    if !choices.split('|').any(|choice| choice == value) {
+                        return Err(ParseError::Invalid(format!("`{}` should be one of {}, not `{}`", arg, choices, value)));
+                    }
+                }
+                matches.flags.insert(flag.name, value);
+            } else {
+                positionals.push(arg);
+            }
+        }
+
+        // Fill the positional slots in order
+        let mut positionals = positionals.into_iter();
+        for arg in &self.args {
+            let value = match arg.kind {
+                ArgKind::Rest => Some(positionals.by_ref().collect::<Vec<String>>().join(" "))
+                        .filter(|rest| !rest.is_empty()),
+                _ => positionals.next()
+            };
+            match value {
+                Some(value) => { matches.args.insert(arg.name, value); },
+                None if arg.kind == ArgKind::Required =>
+                        return Err(ParseError::Invalid(format!("Missing <{}>", arg.name))),
+                None => ()
+            }
+        }
+        if let Some(extra) = positionals.next() {
+            return Err(ParseError::Invalid(format!("Unexpected argument `{}`", extra)));
+        }
+        Ok(matches)
+    }
+}
+
+impl Matches {
+    /// Was the given flag passed?
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains_key(name)
+    }
+
+    /// Get the value passed with the given flag, if it was.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.flags.get(name).and_then(|value| value.as_deref())
+    }
+
+    /// Get the given positional argument, if it was passed.
+    pub fn arg(&self, name: &str) -> Option<&str> {
+        self.args.get(name).map(String::as_str)
+    }
+}
+
+/// Write `contents` to the file at `path`, with a message saying which
+/// file couldn't be written if that fails.
+pub fn write_file(path: &str, contents: &str) -> Result<(), String> {
+    std::fs::write(path, contents).map_err(|e| format!("Unable to write `{}`: {}", path, e))
+}
+
+#[cfg(test)]
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(String::from).collect()
+}
+
+#[test]
+fn test_parse_from() {
+    let command = Command::new("hw3", "Test strings against a DFA")
+            .flag("stream", "Check transitions while loading")
+            .arg("filename", "YAML file")
+            .optional_arg("strings", "File of strings to check");
+
+    let matches = command.parse_from(args("dfa.yaml")).unwrap();
+    assert!(!matches.flag("stream"));
+    assert_eq!(matches.arg("filename"), Some("dfa.yaml"));
+    assert_eq!(matches.arg("strings"), None);
+
+    // flags can go anywhere
+    let matches = command.parse_from(args("dfa.yaml --stream in.txt")).unwrap();
+    assert!(matches.flag("stream"));
+    assert_eq!(matches.arg("strings"), Some("in.txt"));
+
+    // everything after `--` is positional
+    let matches = command.parse_from(args("-- --stream")).unwrap();
+    assert_eq!(matches.arg("filename"), Some("--stream"));
+
+    assert_eq!(command.parse_from(args("dfa.yaml --help")).unwrap_err(), ParseError::Help);
+    assert_eq!(command.parse_from(args("")).unwrap_err(), ParseError::Invalid("Missing <filename>".to_string()));
+    assert_eq!(command.parse_from(args("a b c")).unwrap_err(),
+            ParseError::Invalid("Unexpected argument `c`".to_string()));
+    assert_eq!(command.parse_from(args("--min dfa.yaml")).unwrap_err(),
+            ParseError::Invalid("Unknown flag `--min`".to_string()));
+    assert_eq!(command.parse_from(args("--stream --stream dfa.yaml")).unwrap_err(),
+            ParseError::Invalid("`--stream` was given more than once".to_string()));
+    assert_eq!(command.usage(), "Usage: ./hw3 [--stream] <filename> [strings]");
+}
+
+#[test]
+fn test_options_and_rest() {
+    let command = Command::new("regex", "Convert a regex to a DFA")
+            .option("test", "cases", "Check these cases")
+            .rest("regex", "The regex");
+
+    let matches = command.parse_from(args("a b --test a:1 c")).unwrap();
+    assert_eq!(matches.value("test"), Some("a:1"));
+    assert_eq!(matches.arg("regex"), Some("a b c"));
+
+    let matches = command.parse_from(args("")).unwrap();
+    assert_eq!(matches.arg("regex"), None);
+
+    assert_eq!(command.parse_from(args("ab --test")).unwrap_err(),
+            ParseError::Invalid("Missing <cases> after `--test`".to_string()));
+    assert_eq!(command.usage(), "Usage: ./regex [--test <cases>] <regex...>");
+    assert!(command.help().contains("\n    --test <cases>      Check these cases\n"));
+}
+
+#[test]
+fn test_write_file() {
+    let path = std::env::temp_dir().join("cli_test_write_file.dot");
+    let path = path.to_str().unwrap();
+    write_file(path, "digraph {}").unwrap();
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "digraph {}");
+    std::fs::remove_file(path).unwrap();
+
+    let err = write_file("no_such_dir/out.dot", "").unwrap_err();
+    assert!(err.starts_with("Unable to write `no_such_dir/out.dot`: "), "{}", err);
+}
+
+#[test]
+fn test_choice() {
+    let command = Command::new("hw5", "Draw a PDA")
+            .choice("format", "graphviz|mermaid", "Diagram format")
+            .arg("filename", "YAML file");
+
+    let matches = command.parse_from(args("--format mermaid pda.yaml")).unwrap();
+    assert_eq!(matches.value("format"), Some("mermaid"));
+    assert_eq!(command.parse_from(args("pda.yaml")).unwrap().value("format"), None);
+    assert_eq!(command.parse_from(args("--format dot pda.yaml")).unwrap_err(),
+            ParseError::Invalid("`--format` should be one of graphviz|mermaid, not `dot`".to_string()));
+    assert_eq!(command.usage(), "Usage: ./hw5 [--format <graphviz|mermaid>] <filename>");
+}
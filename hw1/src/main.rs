@@ -1,38 +1,24 @@
+use automata_core::graphviz;
+
 fn main() {
     // collect commandline args
-    let args: Vec<String> = std::env::args().collect();
-    
-    // exit if wrong amount provided
-    if args.len() != 2 {
-        println!("Usage: {} q1,q2,q3,...", args[0]);
-        return;
-    }
+    let args = cli::Command::new("hw1", "Draw a chain of states with GraphViz")
+            .arg("q1,q2,q3,...", "Comma-separated list of states")
+            .parse();
     
     // split nodes into vector
-    let nodes: Vec<&str> = args[1].split(',').collect();
+    let nodes: Vec<&str> = args.arg("q1,q2,q3,...").unwrap().split(',').collect();
 
     println!("{}", build_graph_def(nodes));
 }
 
 fn build_graph_def(nodes: Vec<&str>) -> String {
-    // combine each pair of nodes into Graphviz's "a -> b" format
-    let transitions: String = nodes.windows(2)
-        .map(|pair| format!("{} -> {};\n    ", pair[0], pair[1]))
-        .collect();
+    // connect each pair of neighboring nodes with an unlabeled edge
+    let transitions = nodes.windows(2)
+        .map(|pair| graphviz::Edge::new(pair[0], pair[1]));
 
-    // return the graph definition
-    format!(
-"digraph {{
-    rankdir=LR;
-    node [shape=point]; start;
-    node [shape=doublecircle]; {end_node};
-    node [shape=circle];
-    start -> {start_node};
-    {transitions}
-}}",
-        start_node=nodes[0],
-        end_node=nodes[nodes.len() - 1],
-        transitions=transitions.trim_end())
+    let diagram = graphviz::Diagram::new(nodes[0], vec![nodes[nodes.len() - 1].to_string()]);
+    graphviz::generate(&diagram, transitions)
 }
 
 #[test]
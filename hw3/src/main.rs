@@ -5,10 +5,14 @@
 //! # Usage
 //! 
 //!    ```
-//!     ./hw3 filename
+//!     ./hw3 [--stream] filename [strings]
 //!     ```
 //! 
 //!    where: `filename` is a yaml file containing the DFA definition
+//!           `--stream` checks the transitions while reading them, to fail
+//!           fast on huge files
+//!           `strings` is an optional file of strings to check, one per
+//!           line (otherwise they're read interactively from stdin)
 //! 
 //! # Output
 //! 
@@ -18,42 +22,50 @@
 //! cargo run sample.yaml
 //! ```
 
-use std::io::Write;
-use serde::{Deserialize};
-
-/// # Deterministic Finite Automaton Structure
-/// 
-/// Create a structure that the YAML files will be deserialized into.
-#[derive(Debug, Deserialize)]
-struct DFA {
-    alphabet: Vec<char>,
-    start: u32,
-    accept: Vec<u32>,
-    transitions: Vec<Vec<u32>>,
-    
-    // This field isn't loaded from the YAML file so we need
-    // to provide a default value for it
-    #[serde(default)]
-    n_states: u32
-}
+use automata_core::DFA;
 
 fn main() {
     use std::io::BufRead;
 
-    let filename = get_filename(std::env::args());
+    let args = command().parse();
+    let filename = args.arg("filename").unwrap();
 
     // Load the yaml file getting a Box pointing to a DFA
     // instance on the heap
-    let dfa = DFA::new_from_file(&filename);
+    let dfa = if args.flag("stream") {
+        DFA::new_from_file_streaming(filename)
+    } else {
+        DFA::new_from_file(filename)
+    };
+    let dfa = dfa.unwrap_or_else(|msg| {
+        eprintln!("Failed to parse `{}`: {}", filename, msg);
+        std::process::exit(1);
+    });
 
     // Check DFA for errors
     if let Err(msg) = dfa.validate() {
-        writeln!(std::io::stderr(), "Failed to parse `{}`: {}", filename, msg)
-                    .unwrap();
+        eprintln!("Failed to parse `{}`: {}", filename, msg);
         std::process::exit(1);
     }
 
-    // Get input from stdin
+    // Report anything that's legal but probably a mistake
+    for warning in dfa.warnings() {
+        eprintln!("Warning in `{}`: {}", filename, warning);
+    }
+
+    // Check every string in the batch file, if one was given
+    if let Some(strings) = args.arg("strings") {
+        let results = check_file(&dfa, strings).unwrap_or_else(|msg| {
+            eprintln!("Failed to read `{}`: {}", strings, msg);
+            std::process::exit(1);
+        });
+        for accepted in results {
+            println!("{}", if accepted { "ACCEPT" } else { "REJECT" });
+        }
+        return;
+    }
+
+    // Otherwise get input from stdin
     println!("Enter strings to check if they are accepted or rejected:");
     let stdin = std::io::stdin();
     for line in stdin.lock().lines() {
@@ -62,7 +74,7 @@ fn main() {
             Err(error) => panic!("Error reading from stdin: {}", error),
 
             // Otherwise pass the string to the DFA
-            Ok(line) => println!("{}", match dfa.accepts(&line) {
+            Ok(line) => println!("{}", match dfa.run(&line, true) {
                 true => "ACCEPT",
                 false => "REJECT"
             })
@@ -71,114 +83,37 @@ fn main() {
     }
 }
 
-/// Get the filename passed as the first parameter
-fn get_filename(args: std::env::Args) -> String {
-    // Get the arguments as a vector
-    let args: Vec<String> = args.collect();
-
-    // Make sure only one argument was passed
-    if args.len() != 2 {
-        writeln!(std::io::stderr(), "Usage: ./hw2 <filename.yaml>")
-                    .unwrap();
-        std::process::exit(1);
-    }
-    
-    args[1].to_string()
-}  
-
-impl DFA {
-    /// Load the .yaml file specified into a DFA structure
-    /// on the heap and return a point to it via a Box.
-    fn new_from_file(filename: &str) -> Box<DFA> {
-        let file = std::fs::File::open(filename)
-                    .expect("Unable to open input");
-
-        // Deserialize using serde
-        let mut dfa: DFA = serde_yaml::from_reader(file)
-                    .expect("Unable to parse yaml");
-        
-        // Compute number of states
-        dfa.n_states = dfa.transitions.len() as u32;
-
-        Box::new(dfa)
-    }
-
-    /// Check whether this DFA is well-formed.
-    fn validate(&self) -> Result<(), String> {
-        let alphabet_len = self.alphabet.len();
-        let out_of_range = |s| !(1..=self.n_states).contains(s);
-
-        // Check start state
-        if out_of_range(&self.start) {
-            return Err(format!("Unknown start state `{}`", self.start));
-        }
-
-        // Check final states
-        for final_state in &self.accept {
-            if out_of_range(final_state) {
-                return Err(format!("Unknown final state `{}`", final_state));
-            }
-        }
-
-        // Check transitions
-        for (state, dest_states) in &mut self.transitions.iter().enumerate() {
-            // Check number of transitions
-            if dest_states.len() != alphabet_len {
-                return Err(format!("State `{}` defines {} transitions (should define {})",
-                        state + 1, dest_states.len(), alphabet_len));
-            }
-
-            // Check transition destinations
-            for dest_state in dest_states {
-                if out_of_range(&dest_state) {
-                    return Err(format!("State `{}` cannot transition to unknown state `{}`",
-                            state + 1, &dest_state));
-                }
-            }
-        }
-
-        return Ok(());
-    }
-
-    /// Check whether this DFA accepts the given string.
-    fn accepts(&self, input: &str) -> bool {
-        let mut state = self.start;
-        for letter in input.chars() {
-            // Get the transition index for this letter
-            let transition = self.alphabet.iter().position(|&ltr| ltr == letter);
+/// Describe the command line arguments
+fn command() -> cli::Command {
+    cli::Command::new("hw3", "Check which strings a DFA (or NFA) accepts")
+            .flag("stream", "Check the transitions while reading them, to fail fast on huge files")
+            .arg("filename", "YAML file containing the DFA definition")
+            .optional_arg("strings", "File of strings to check, one per line (default: read stdin)")
+}
 
-            // Follow the transition to the next state
-            let new_state = match transition {
-                None => panic!("Cannot parse string with non-alphabet letters"),
-                Some(index) => self.transitions[state as usize - 1][index]
-            };
+/// Check each line of the given file, without printing any transitions.
+fn check_file(dfa: &DFA, filename: &str) -> Result<Vec<bool>, String> {
+    use std::io::BufRead;
 
-            // Print the transition and actually update the state
-            println!("  \u{3B4}(q{}, {}) \u{2192} q{}", state, letter, new_state);
-            state = new_state;
-        }
-        self.accept.contains(&state)
-    }
+    let file = std::fs::File::open(filename).map_err(|e| e.to_string())?;
+    std::io::BufReader::new(file).lines()
+        .map(|line| line.map(|line| dfa.run(&line, false)).map_err(|e| e.to_string()))
+        .collect()
 }
 
 #[test]
-fn test_accept() {
-    // Accepts strings of {a, b} that end with a b
+fn test_check_file() {
     let dfa = DFA {
-        alphabet: vec!['a', 'b'],
+        alphabet: vec!["a".to_string(), "b".to_string()],
         start: 1,
         accept: vec![2],
         transitions: vec![vec![2, 1], vec![2, 1]],
-        n_states: 2
+        names: vec![],
+        n_states: 2,
+        unused_symbols: vec![],
+        undeclared_symbols: vec![]
     };
-    // positive inputs
-    assert!(dfa.accepts("a"));
-    assert!(dfa.accepts("baa"));
-    assert!(dfa.accepts("baba"));
-
-    // negative inputs
-    assert!(!dfa.accepts(""));
-    assert!(!dfa.accepts("b"));
-    assert!(!dfa.accepts("ab"));
-    assert!(!dfa.accepts("abab"));
-}
\ No newline at end of file
+    let strings = concat!(env!("CARGO_MANIFEST_DIR"), "/strings.txt");
+    assert_eq!(check_file(&dfa, strings), Ok(vec![true, true, false, false, false, false]));
+    assert!(check_file(&dfa, "no_such_file.txt").is_err());
+}
@@ -5,66 +5,108 @@
 //! # Usage
 //! 
 //!    ```
-//!     ./hw5 filename
+//!     ./hw5 [--output file] [--format graphviz|mermaid] filename
 //!     ```
 //! 
 //!    where: `filename` is a yaml file containing the PDA definition
+//!           `--output` writes just the diagram to `file`
+//!           `--format` picks GraphViz (the default) or a Mermaid state diagram
 //! 
 //! # Output
 //! 
-//! Debugging output is produced to `stdout`. Build and run using:
+//! Debugging output is produced to `stdout`, followed by a prompt to enter
+//! strings to run through the PDA. Build and run using:
 //! 
 //! ```
 //! cargo run sample.yaml
 //! ```
 
-use std::io::Write;
+#![allow(clippy::upper_case_acronyms)]
+
+use automata_core::graphviz;
+use automata_core::nfa::NFA;
+use automata_core::DFA;
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+
+/// Give up on any configuration whose stack grows past this many symbols, so
+/// a PDA that keeps pushing on epsilon moves can't run forever
+const STACK_LIMIT: usize = 1000;
 
 /// # Push Down Automaton
 /// 
 /// Create a structure that the YAML files will be deserialized into.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 struct PDA {
     alphabet: Vec<String>,
     stack_alphabet: Vec<String>,
     start: usize,
     accept: Vec<usize>,
+    #[serde(default)]
+    accept_by: AcceptBy,
     transitions: Vec<Vec<Transition>>,
 }
 
+/// # Acceptance Mode
+///
+/// How a PDA decides to accept once it has read all of its input: by being
+/// in one of its `accept` states, or by having an empty stack (in which case
+/// it shouldn't list any `accept` states).
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum AcceptBy {
+    #[default]
+    FinalState,
+    EmptyStack
+}
+
+/// # Raw PDA Structure
+///
+/// The PDA exactly as it appears in the YAML file. Transitions are kept as
+/// plain YAML values until we've checked that each one has 4 fields, since
+/// serde's own error for a wrong-sized tuple doesn't say where it was.
+#[derive(Deserialize)]
+struct RawPDA {
+    alphabet: Vec<String>,
+    stack_alphabet: Vec<String>,
+    start: usize,
+    accept: Vec<usize>,
+    #[serde(default)]
+    accept_by: AcceptBy,
+    transitions: Vec<Vec<serde_yaml::Value>>,
+}
+
 /// # Transition Structure
 ///
 /// Represent a 4-tuple: (input_char, top_of_stack, push_to_stack, new_state)
 #[derive(Clone, Debug, Deserialize)]
 struct Transition(String, String, String, usize);
 
-/// # Graph Structure
-/// 
-/// Contains a vector of nodes and the index of the start node.
-#[derive(Debug)]
-struct Graph {
-    nodes: Vec<Node>,
-    start_node: usize
-}
-
-/// # Node Structure
-/// 
-/// Contains a vector of all connected node indices, the transition
-/// labels for those connections (can be left undefined), and a
-/// boolean indicating whether this is a final node.
-#[derive(Debug)]
-struct Node {
-    connections: Vec<Transition>,
-    accept_state: bool
+/// # Configuration Structure
+///
+/// A snapshot of a running PDA: its current state, the input it has left
+/// to read, and its stack (with the top at the end).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Config {
+    state: usize,
+    remaining: String,
+    stack: Vec<String>
 }
 
 fn main() {
-    let filename = get_filename(std::env::args());
+    let args = command().parse();
+    let filename = args.arg("filename").unwrap();
 
     // Load the yaml file getting a Box pointing to a PDA
     // instance on the heap
-    let pda = PDA::new_from_file(&filename);
+    let pda = match PDA::new_from_file(filename) {
+        Ok(pda) => pda,
+        Err(msg) => {
+            eprintln!("Failed to parse `{}`: {}", filename, msg);
+            std::process::exit(1);
+        }
+    };
 
     // Check PDA for errors
     if let Err(msg) = pda.validate() {
@@ -72,46 +114,332 @@ fn main() {
         std::process::exit(1);
     }
 
-    // Convert to Graph and display in stdout
-    let graph = pda.to_graph();
-    println!("\nGraphViz definition:\n");
-    graph.print_graphviz();
-    println!("\nDebug printed graph structure:\n");
-    graph.print();
-}
+    // Let the user know if runs may have to guess
+    if !pda.is_deterministic() {
+        println!("Note: `{}` describes a nondeterministic PDA", filename);
+    }
 
-/// Get the filename passed as the first parameter
-fn get_filename(args: std::env::Args) -> String {
-    // Get the arguments as a vector
-    let args: Vec<String> = args.collect();
+    // Draw the PDA and either save it or display it in stdout
+    let mermaid = args.value("format") == Some("mermaid");
+    if let Some(path) = args.value("output") {
+        let diagram = if mermaid { pda.to_mermaid() } else { pda.to_graphviz() };
+        cli::write_file(path, &diagram).unwrap_or_else(|msg| {
+            eprintln!("{}", msg);
+            std::process::exit(1);
+        });
+    } else {
+        if mermaid {
+            println!("\nMermaid definition:\n");
+            println!("{}", pda.to_mermaid());
+        } else {
+            println!("\nGraphViz definition:\n");
+            println!("{}", pda.to_graphviz());
+        }
+        println!("\nDebug printed PDA structure:\n");
+        println!("{:?}", pda);
+    }
 
-    // Make sure only one argument was passed
-    if args.len() != 2 {
-        writeln!(std::io::stderr(), "Usage: ./hw5 <filename.yaml>")
-                    .unwrap();
-        std::process::exit(1);
+    // Run user given strings through the PDA
+    println!("\nEnter strings to check if they are accepted or rejected:");
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        match line {
+            // Panic on an error
+            Err(error) => panic!("Error reading from stdin: {}", error),
+
+            // Otherwise pass the string to the PDA, showing how it was accepted
+            Ok(line) => match pda.trace(&line) {
+                Some(run) => {
+                    for config in run {
+                        println!("  {}", config);
+                    }
+                    println!("ACCEPT")
+                },
+                None => println!("REJECT")
+            }
+        }
     }
-    
-    args[1].to_string()
-}  
+}
 
-/// Check if a letter is in the given alphabet
-fn in_alphabet(ltr: &String, alphabet: &Vec<String>) -> bool {
+/// Describe the command line arguments
+fn command() -> cli::Command {
+    cli::Command::new("hw5", "Draw a PDA from a YAML file with GraphViz and run strings through it")
+            .option("output", "file", "Write only the diagram to this file")
+            .choice("format", "graphviz|mermaid", "Diagram format (default: graphviz)")
+            .arg("filename", "YAML file containing the PDA definition")
+}
+
+/// Check if a letter is in the given alphabet (or is epsilon)
+fn in_alphabet(ltr: &String, alphabet: &[String]) -> bool {
     ltr.is_empty() || alphabet.contains(ltr)
 }
 
 impl PDA {
     /// Load the .yaml file specified into a PDA structure
     /// on the heap and return a point to it via a Box.
-    fn new_from_file(filename: &str) -> Box<PDA> {
+    fn new_from_file(filename: &str) -> Result<Box<PDA>, String> {
         let file = std::fs::File::open(filename)
-                .expect("Unable to open input");
+                .map_err(|e| format!("Unable to open input: {}", e))?;
+        PDA::from_reader(file)
+    }
 
+    /// Deserialize a PDA from YAML, reporting exactly which transition
+    /// is malformed if one doesn't have 4 fields.
+    fn from_reader<R: std::io::Read>(reader: R) -> Result<Box<PDA>, String> {
         // Deserialize using serde
-        let pda: PDA = serde_yaml::from_reader(file)
-                .expect("Unable to parse yaml");
+        let raw: RawPDA = serde_yaml::from_reader(reader)
+                .map_err(|e| format!("Unable to parse yaml: {}", e))?;
+
+        // Convert each transition now that we know its shape
+        let mut transitions = vec![];
+        for (state, raw_transitions) in raw.transitions.into_iter().enumerate() {
+            let mut converted = vec![];
+            for (index, value) in raw_transitions.into_iter().enumerate() {
+                let location = format!("State {} transition {}", state + 1, index + 1);
+                match &value {
+                    serde_yaml::Value::Sequence(fields) if fields.len() != 4 => {
+                        return Err(format!("{} has {} fields, expected 4", location, fields.len()));
+                    },
+                    serde_yaml::Value::Sequence(_) => (),
+                    _ => return Err(format!("{} is not a list", location))
+                }
+                converted.push(serde_yaml::from_value(value)
+                        .map_err(|e| format!("{} is invalid: {}", location, e))?);
+            }
+            transitions.push(converted);
+        }
+
+        Ok(Box::new(PDA {
+            alphabet: raw.alphabet,
+            stack_alphabet: raw.stack_alphabet,
+            start: raw.start,
+            accept: raw.accept,
+            accept_by: raw.accept_by,
+            transitions
+        }))
+    }
+
+    /// Build a PDA that recognizes the same language as a DFA (as loaded
+    /// by homework 2) by never touching the stack.
+    #[allow(dead_code)]
+    fn from_dfa(dfa: &DFA) -> PDA {
+        let transitions = dfa.transitions.iter()
+                .map(|next_states| dfa.alphabet.iter()
+                    .zip(next_states)
+                    .map(|(symbol, &next)| Transition(symbol.clone(), "".to_string(), "".to_string(), next as usize))
+                    .collect())
+                .collect();
+        PDA {
+            alphabet: dfa.alphabet.clone(),
+            stack_alphabet: vec![],
+            start: dfa.start as usize,
+            accept: dfa.accept.iter().map(|&state| state as usize).collect(),
+            accept_by: AcceptBy::FinalState,
+            transitions
+        }
+    }
+
+    /// Build a PDA that recognizes this PDA's language followed by another's.
+    /// The other PDA's states are numbered after this one's, and each of this
+    /// PDA's accept states gets an epsilon transition to the other's start.
+    /// Whatever this PDA leaves on the stack is still there for the other one.
+    /// If this PDA accepts by empty stack, it's converted to accept by final
+    /// state first (see `to_final_state`); the other one can accept either way.
+    #[allow(dead_code)]
+    fn concat(&self, other: &PDA) -> PDA {
+        if self.accept_by == AcceptBy::EmptyStack {
+            return self.to_final_state().concat(other);
+        }
+        let offset = self.transitions.len();
+        let union = |first: &[String], second: &[String]| {
+            let mut symbols = first.to_vec();
+            symbols.extend(second.iter().filter(|symbol| !first.contains(symbol)).cloned());
+            symbols
+        };
+
+        let mut transitions = self.transitions.clone();
+        for &state in &self.accept {
+            let epsilon = Transition("".to_string(), "".to_string(), "".to_string(), other.start + offset);
+            transitions[state - 1].push(epsilon);
+        }
+        transitions.extend(other.transitions.iter()
+                .map(|state| state.iter()
+                    .map(|trans| Transition(trans.0.clone(), trans.1.clone(), trans.2.clone(), trans.3 + offset))
+                    .collect()));
+
+        PDA {
+            alphabet: union(&self.alphabet, &other.alphabet),
+            stack_alphabet: union(&self.stack_alphabet, &other.stack_alphabet),
+            start: self.start,
+            accept: other.accept.iter().map(|state| state + offset).collect(),
+            accept_by: other.accept_by,
+            transitions
+        }
+    }
+
+    /// Build an equivalent PDA that accepts by final state. A PDA that
+    /// accepts by empty stack gets a new start state that pushes a fresh
+    /// bottom marker, and every state can pop that marker to reach a new
+    /// accept state, which it can only do once the original stack is empty.
+    fn to_final_state(&self) -> PDA {
+        if self.accept_by == AcceptBy::FinalState {
+            return self.clone();
+        }
+        let mut marker = "\u{22A5}".to_string();
+        while self.stack_alphabet.contains(&marker) {
+            marker.push('\'');
+        }
+        let (start, accept) = (self.transitions.len() + 1, self.transitions.len() + 2);
+
+        let mut transitions = self.transitions.clone();
+        for state in &mut transitions {
+            state.push(Transition("".to_string(), marker.clone(), "".to_string(), accept));
+        }
+        transitions.push(vec![Transition("".to_string(), "".to_string(), marker.clone(), self.start)]);
+        transitions.push(vec![]);
+
+        let mut stack_alphabet = self.stack_alphabet.clone();
+        stack_alphabet.push(marker);
+        PDA {
+            alphabet: self.alphabet.clone(),
+            stack_alphabet,
+            start,
+            accept: vec![accept],
+            accept_by: AcceptBy::FinalState,
+            transitions
+        }
+    }
+
+    /// Does this PDA leave the stack alone on every transition? If so, it's
+    /// really a finite automaton and recognizes a regular language.
+    #[allow(dead_code)]
+    fn is_regular_shaped(&self) -> bool {
+        self.transitions.iter()
+                .flatten()
+                .all(|trans| trans.1.is_empty() && trans.2.is_empty())
+    }
 
-        Box::new(pda)
+    /// Extract the finite automaton from a PDA that never uses its stack,
+    /// or None if it does use it. Transitions on an empty input become the
+    /// NFA's epsilon transitions. If this PDA accepts by empty stack, every
+    /// state accepts, since the stack is never anything but empty.
+    #[allow(dead_code)]
+    fn to_nfa(&self) -> Option<NFA> {
+        if !self.is_regular_shaped() {
+            return None;
+        }
+        let mut transitions = vec![vec![vec![]; self.alphabet.len()]; self.transitions.len()];
+        let mut epsilon = vec![];
+        for (state, state_transitions) in self.transitions.iter().enumerate() {
+            for trans in state_transitions {
+                // (validate rejects input symbols outside the alphabet)
+                match self.alphabet.iter().position(|symbol| *symbol == trans.0) {
+                    Some(column) => transitions[state][column].push(trans.3 as u32),
+                    None if trans.0.is_empty() => epsilon.push((state as u32 + 1, trans.3 as u32)),
+                    None => ()
+                }
+            }
+        }
+        Some(NFA {
+            alphabet: self.alphabet.clone(),
+            start: self.start as u32,
+            accept: match self.accept_by {
+                AcceptBy::FinalState => self.accept.iter().map(|&state| state as u32).collect(),
+                AcceptBy::EmptyStack => (1..=self.transitions.len() as u32).collect()
+            },
+            transitions,
+            epsilon
+        })
+    }
+
+    /// Is there never more than one transition to choose from? Two
+    /// transitions out of the same state conflict if one's input could be
+    /// read wherever the other's could (they're equal, or one is a prefix
+    /// of the other, like epsilon) and the same goes for the stack symbols
+    /// they pop. Nondeterministic PDAs are still valid, they just guess.
+    fn is_deterministic(&self) -> bool {
+        let overlap = |a: &String, b: &String| a.starts_with(b.as_str()) || b.starts_with(a.as_str());
+        self.transitions.iter().all(|state| {
+            state.iter().enumerate().all(|(i, first)| {
+                state[i + 1..].iter().all(|second| !(overlap(&first.0, &second.0) && overlap(&first.1, &second.1)))
+            })
+        })
+    }
+
+    /// Check whether this PDA accepts a string, i.e. whether some run reads
+    /// all of the input and ends in a final state (or with an empty stack,
+    /// depending on `accept_by`). `main` prints the whole run with `trace`
+    /// instead, so this is only built for the tests.
+    #[cfg(test)]
+    fn accepts(&self, input: &str) -> bool {
+        self.trace(input).is_some()
+    }
+
+    /// Find an accepting run for a string, listing every configuration along
+    /// the way from the start, or None if the string is rejected.
+    /// Every nondeterministic choice is explored with a worklist, and a
+    /// configuration that has already been seen isn't explored again, so
+    /// epsilon loops that don't change the stack terminate.
+    fn trace(&self, input: &str) -> Option<Vec<Config>> {
+        let start = Config {state: self.start, remaining: input.to_string(), stack: vec![]};
+
+        // remember how we got to each configuration to rebuild the run
+        let mut parents: HashMap<Config, Config> = HashMap::new();
+        let mut visited: HashSet<Config> = [start.clone()].iter().cloned().collect();
+        let mut worklist = vec![start];
+        while let Some(config) = worklist.pop() {
+            if self.is_accepting(&config) {
+                let mut run = vec![config];
+                while let Some(parent) = parents.get(run.last().unwrap()) {
+                    run.push(parent.clone());
+                }
+                run.reverse();
+                return Some(run);
+            }
+            for next in self.successors(&config) {
+                if visited.insert(next.clone()) {
+                    parents.insert(next.clone(), config.clone());
+                    worklist.push(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Is this a configuration the PDA can stop and accept in?
+    fn is_accepting(&self, config: &Config) -> bool {
+        config.remaining.is_empty() && match self.accept_by {
+            AcceptBy::FinalState => self.accept.contains(&config.state),
+            AcceptBy::EmptyStack => config.stack.is_empty()
+        }
+    }
+
+    /// Get every configuration reachable from this one in a single move.
+    /// An empty input or stack symbol in a transition is epsilon, so it
+    /// doesn't need to match anything.
+    fn successors(&self, config: &Config) -> Vec<Config> {
+        let mut successors = vec![];
+        for trans in &self.transitions[config.state - 1] {
+            if !config.remaining.starts_with(&trans.0) {
+                continue;
+            }
+            let mut stack = config.stack.clone();
+            if !trans.1.is_empty() && stack.pop().as_ref() != Some(&trans.1) {
+                continue;
+            }
+            if !trans.2.is_empty() {
+                stack.push(trans.2.clone());
+            }
+            if stack.len() > STACK_LIMIT {
+                continue;
+            }
+            successors.push(Config {
+                state: trans.3,
+                remaining: config.remaining[trans.0.len()..].to_string(),
+                stack
+            });
+        }
+        successors
     }
 
     /// Check whether this PDA is well-formed.
@@ -125,13 +453,26 @@ impl PDA {
         }
 
         // Check final states
+        if self.accept_by == AcceptBy::EmptyStack && !self.accept.is_empty() {
+            return Err("A PDA that accepts by empty stack cannot have final states".to_string());
+        }
         for final_state in &self.accept {
             if out_of_range(final_state) {
                 return Err(format!("Unknown final state `{}`", final_state));
             }
         }
 
+        // The empty string is reserved for epsilon, so it can't be a symbol
+        if self.alphabet.iter().any(String::is_empty) {
+            return Err("Alphabet cannot contain the empty string (it represents epsilon)".to_string());
+        }
+        if self.stack_alphabet.iter().any(String::is_empty) {
+            return Err("Stack alphabet cannot contain the empty string (it represents epsilon)".to_string());
+        }
+
         // Check transitions
+        // (empty fields are epsilon, so a transition with all three empty is
+        // allowed and just changes state)
         for (state, transitions) in self.transitions.iter().enumerate() {
             for trans in transitions {
                 if !in_alphabet(&trans.0, &self.alphabet) {
@@ -149,112 +490,412 @@ impl PDA {
             }
         }
 
-        return Ok(());
-    }
-
-    /// Generate a Graph structure from this PDA.
-    fn to_graph(&self) -> Box<Graph> {
-        // Create vec of nodes
-        let mut nodes = vec![];
-        for (state, transitions) in self.transitions.iter().enumerate() {
-            nodes.push(Node {
-                connections: transitions.to_vec(),
-                accept_state: self.accept.contains(&(state + 1))
-            })
-        }
-
-        // Insert start node
-        let start_trans = Transition("".to_string(), "".to_string(), "".to_string(), self.start);
-        nodes.insert(0, Node {connections: vec![start_trans], accept_state: false});
-
-        Box::new(Graph {nodes, start_node: 0})
+        Ok(())
     }
 }
 
-impl Graph {
-    /// Print this graph in debug format to stdout.
-    fn print(&self) {
-        println!("{:?}", self); // could use {:#?} for pretty printing
+impl std::fmt::Display for Config {
+    /// Show a configuration as (state, remaining input, stack), e.g.
+    /// `(q2, 11, 0 0)` with the top of the stack on the right
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let remaining = if self.remaining.is_empty() { "\u{3B5}" } else { &self.remaining };
+        let stack = if self.stack.is_empty() { "\u{3B5}".to_string() } else { self.stack.join(" ") };
+        write!(f, "(q{}, {}, {})", self.state, remaining, stack)
     }
+}
 
-    /// Print this graph in GraphViz format to stdout.
-    fn print_graphviz(&self) {
-        println!("{}", self.to_graphviz());
+impl Transition {
+    /// Does this transition ignore both the input and the stack?
+    fn is_epsilon(&self) -> bool {
+        self.0.is_empty() && self.1.is_empty() && self.2.is_empty()
     }
+}
 
-    /// Return this graph as a string in GraphViz format.
+impl PDA {
+    /// Return this PDA as a string in GraphViz format.
     fn to_graphviz(&self) -> String {
-        let mut transitions = vec![];
+        let check_epsilon = |ltr: &str| if ltr.is_empty() { "&epsilon;".to_string() } else { ltr.to_string() };
+        let node_id = |num: usize| format!("q{}", num);
 
-        let check_epsilon = |ltr: String| if ltr.is_empty() { "&epsilon;".to_string() } else { ltr } ;
+        let mut diagram = graphviz::Diagram::new(
+            node_id(self.start),
+            self.accept.iter().map(|&state| node_id(state)).collect()
+        );
+        // Labels contain commas, so merged ones go on separate lines
+        diagram.separator = "\\n";
 
         // Build list of transitions
-        for (num, node) in self.nodes.iter().enumerate() {
-            for trans in &node.connections {
-                let input = check_epsilon(trans.0.clone());
-                let pop_stack = check_epsilon(trans.1.clone());
-                let push_stack = check_epsilon(trans.2.clone());
-                transitions.push(format!("q{} -> q{} [label=\"{}, {} &rarr; {}\"]", num, trans.3, input, pop_stack, push_stack));
+        let mut transitions = vec![];
+        for (state, state_transitions) in self.transitions.iter().enumerate() {
+            for trans in state_transitions {
+                // Spontaneous state changes get a dashed edge
+                if trans.is_epsilon() {
+                    transitions.push(graphviz::Edge::labeled(node_id(state + 1), node_id(trans.3), "&epsilon;").dashed());
+                    continue;
+                }
+
+                let label = format!("{}, {} &rarr; {}", check_epsilon(&trans.0), check_epsilon(&trans.1), check_epsilon(&trans.2));
+                transitions.push(graphviz::Edge::labeled(node_id(state + 1), node_id(trans.3), label));
             }
         }
 
-        // Build string of accept nodes
-        let accept_nodes = self.nodes.iter()
-                .enumerate()
-                .filter(|node| node.1.accept_state)
-                .map(|node| format!("q{}", node.0))
-                .collect::<Vec<String>>()
-                .join("; ");
-        
-        format!(
-            "digraph {{\n\
-                rankdir=LR;\n\
-                node [shape=point]; q{};\n\
-                node [shape=doublecircle]; {};\n\
-                node [shape=circle];\n\
-                {};\n\
-            }}",
-            self.start_node,
-            accept_nodes,
-            transitions.join(";\n")
-        )
+        graphviz::generate(&diagram, transitions)
     }
-}
 
-// impl Node {
-//     /// Create a new node with the specified transition labels.
-//     fn new(labels: &Vec<char>) -> Node {
-//         let label_refs = labels.iter()
-//                 .map(|&lbl| Some(lbl))
-//                 .collect::<Vec<Option<char>>>();
-//         Node::new_empty()
-//     }
-
-//     /// Create a new empty node.
-//     fn new_empty() -> Node {
-//         Node {
-//             connections: vec![],
-//             accept_state: false
-//         }
-//     }
-// }
+    /// Return this PDA as a Mermaid state diagram, for docs that can't
+    /// run GraphViz. Epsilon is written as `ε` since Mermaid doesn't
+    /// understand HTML entities in labels.
+    fn to_mermaid(&self) -> String {
+        let check_epsilon = |ltr: &str| if ltr.is_empty() { "ε".to_string() } else { ltr.to_string() };
+
+        let mut lines = vec!["stateDiagram-v2".to_string(), format!("    [*] --> q{}", self.start)];
+        for (state, state_transitions) in self.transitions.iter().enumerate() {
+            let num = state + 1;
+            for trans in state_transitions {
+                if trans.is_epsilon() {
+                    lines.push(format!("    q{} --> q{}: ε", num, trans.3));
+                } else {
+                    lines.push(format!("    q{} --> q{}: {}, {} → {}", num, trans.3,
+                            check_epsilon(&trans.0), check_epsilon(&trans.1), check_epsilon(&trans.2)));
+                }
+            }
+            if self.accept.contains(&num) {
+                lines.push(format!("    q{} --> [*]", num));
+            }
+        }
+        lines.join("\n")
+    }
+}
 
 #[test]
 fn test_to_graphviz() {
-    let pda = PDA::new_from_file("sample.yaml");
-    let graph = pda.to_graph();
-    assert_eq!(graph.to_graphviz(),
+    let pda = PDA::new_from_file("sample.yaml").unwrap();
+    assert_eq!(pda.to_graphviz(),
 "digraph {
-rankdir=LR;
-node [shape=point]; q0;
-node [shape=doublecircle]; q2;
-node [shape=circle];
-q0 -> q1 [label=\"&epsilon;, &epsilon; &rarr; &epsilon;\"];
-q1 -> q1 [label=\"0, &epsilon; &rarr; 0\"];
-q1 -> q1 [label=\"1, &epsilon; &rarr; 1\"];
-q1 -> q2 [label=\"&epsilon;, &epsilon; &rarr; &epsilon;\"];
-q2 -> q2 [label=\"0, 0 &rarr; &epsilon;\"];
-q2 -> q2 [label=\"1, 1 &rarr; &epsilon;\"];
+    rankdir=LR;
+    node [shape=point]; start;
+    node [shape=doublecircle]; q2;
+    node [shape=circle];
+    start -> q1;
+    q1 -> q1 [label=\"0, &epsilon; &rarr; 0\\n1, &epsilon; &rarr; 1\"];
+    q1 -> q2 [label=\"&epsilon;\", style=dashed];
+    q2 -> q2 [label=\"0, 0 &rarr; &epsilon;\\n1, 1 &rarr; &epsilon;\"];
 }"
     );
 }
+
+#[test]
+fn test_to_mermaid() {
+    let pda = PDA::new_from_file("sample.yaml").unwrap();
+    assert_eq!(pda.to_mermaid(),
+"stateDiagram-v2
+    [*] --> q1
+    q1 --> q1: 0, ε → 0
+    q1 --> q1: 1, ε → 1
+    q1 --> q2: ε
+    q2 --> q2: 0, 0 → ε
+    q2 --> q2: 1, 1 → ε
+    q2 --> [*]");
+}
+
+#[test]
+fn test_validate_empty_symbols() {
+    let mut pda = PDA::new_from_file("sample.yaml").unwrap();
+    assert!(pda.validate().is_ok());
+
+    // an empty stack symbol would be indistinguishable from epsilon
+    pda.stack_alphabet.push("".to_string());
+    assert_eq!(pda.validate(),
+            Err("Stack alphabet cannot contain the empty string (it represents epsilon)".to_string()));
+
+    pda.stack_alphabet.pop();
+    pda.alphabet.push("".to_string());
+    assert_eq!(pda.validate(),
+            Err("Alphabet cannot contain the empty string (it represents epsilon)".to_string()));
+}
+
+#[test]
+fn test_transition_arity() {
+    let yaml = "
+alphabet: ['0', '1']
+stack_alphabet: ['0']
+start: 1
+accept: [2]
+transitions:
+  - - ['0', '', '0', 1]
+    - ['', '', 2]
+  - []
+";
+    assert_eq!(PDA::from_reader(yaml.as_bytes()).unwrap_err(),
+            "State 1 transition 2 has 3 fields, expected 4");
+
+    let yaml = yaml.replace("['', '', 2]", "['', '', '', 2, 3]");
+    assert_eq!(PDA::from_reader(yaml.as_bytes()).unwrap_err(),
+            "State 1 transition 2 has 5 fields, expected 4");
+
+    let yaml = yaml.replace("['', '', '', 2, 3]", "['', '', '', 2]");
+    assert!(PDA::from_reader(yaml.as_bytes()).is_ok());
+}
+
+#[test]
+fn test_concat_dfa() {
+    // a*
+    let dfa: DFA = serde_yaml::from_str("
+alphabet: [a]
+start: 1
+accept: [1]
+transitions:
+  - [1]
+").unwrap();
+
+    // balanced parentheses, using $ to mark the bottom of the stack
+    let parens = PDA::from_reader("
+alphabet: ['(', ')']
+stack_alphabet: [x, $]
+start: 1
+accept: [3]
+transitions:
+  - - ['', '', $, 2]
+  - - ['(', '', x, 2]
+    - [')', x, '', 2]
+    - ['', $, '', 3]
+  - []
+".as_bytes()).unwrap();
+
+    let pda = PDA::from_dfa(&dfa).concat(&parens);
+    assert!(pda.validate().is_ok());
+    assert_eq!(pda.alphabet, vec!["a", "(", ")"]);
+    assert_eq!(pda.stack_alphabet, vec!["x", "$"]);
+    assert_eq!((pda.start, pda.accept.clone()), (1, vec![4]));
+
+    // the a* state loops on `a`, then jumps to the parentheses' start
+    let state = &pda.transitions[0];
+    assert_eq!(state.len(), 2);
+    assert_eq!((state[0].0.as_str(), state[0].3), ("a", 1));
+    assert!(state[1].is_epsilon());
+    assert_eq!(state[1].3, 2);
+    assert_eq!(pda.transitions[2][2].3, 4);
+
+    for accepted in &["", "aa()", "a(())()", "()"] {
+        assert!(pda.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["(a", "a)(", "()a", "a("] {
+        assert!(!pda.accepts(rejected), "should reject {:?}", rejected);
+    }
+}
+
+#[test]
+fn test_is_regular_shaped() {
+    // the sample matches even-length palindromes, so it needs its stack
+    let pda = PDA::new_from_file("sample.yaml").unwrap();
+    assert!(!pda.is_regular_shaped());
+    assert!(pda.to_nfa().is_none());
+
+    // 0*1, never touching the stack
+    let pda = PDA::from_reader("
+alphabet: ['0', '1']
+stack_alphabet: ['0']
+start: 1
+accept: [3]
+transitions:
+  - - ['0', '', '', 1]
+    - ['', '', '', 2]
+  - - ['1', '', '', 3]
+  - []
+".as_bytes()).unwrap();
+    assert!(pda.is_regular_shaped());
+    let nfa = pda.to_nfa().unwrap();
+    assert_eq!(nfa.alphabet, vec!["0", "1"]);
+    assert_eq!((nfa.start, nfa.accept.clone()), (1, vec![3]));
+    assert_eq!(nfa.transitions[0], vec![vec![1], vec![]]);
+    assert_eq!(nfa.transitions[1], vec![vec![], vec![3]]);
+    assert_eq!(nfa.epsilon, vec![(1, 2)]);
+    assert!(nfa.validate().is_ok());
+
+    // so it can go through the subset construction like any other NFA
+    let dfa = nfa.to_dfa();
+    assert!(dfa.accepts("001"));
+    assert!(!dfa.accepts("010"));
+
+    // without a stack to empty, an empty stack PDA accepts in every state
+    let yaml = "
+alphabet: ['0']
+stack_alphabet: []
+start: 1
+accept: []
+accept_by: empty_stack
+transitions:
+  - - ['0', '', '', 2]
+  - []
+";
+    let nfa = PDA::from_reader(yaml.as_bytes()).unwrap().to_nfa().unwrap();
+    assert_eq!(nfa.accept, vec![1, 2]);
+    assert!(nfa.to_dfa().accepts("0"));
+}
+
+#[test]
+fn test_accepts() {
+    let pda = PDA::new_from_file("0n1n.yaml").unwrap();
+    assert!(pda.validate().is_ok());
+    for accepted in &["", "01", "0011", "000111"] {
+        assert!(pda.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["011", "10", "001", "0101", "1"] {
+        assert!(!pda.accepts(rejected), "should reject {:?}", rejected);
+    }
+
+    // sample.yaml doesn't need an empty stack to accept, so whatever it has
+    // pushed can be left behind
+    let sample = PDA::new_from_file("sample.yaml").unwrap();
+    for input in &["", "0110", "011", "10"] {
+        assert!(sample.accepts(input), "should accept {:?}", input);
+    }
+}
+
+#[test]
+fn test_accepts_epsilon_loops() {
+    // state 1 bounces to state 2 and back on epsilon, and state 2 pushes
+    // on epsilon forever, but only `a` is accepted
+    let pda = PDA::from_reader("
+alphabet: [a]
+stack_alphabet: [x]
+start: 1
+accept: [3]
+transitions:
+  - - ['', '', '', 2]
+    - [a, '', '', 3]
+  - - ['', '', '', 1]
+    - ['', '', x, 2]
+  - []
+".as_bytes()).unwrap();
+    assert!(pda.accepts("a"));
+    assert!(!pda.accepts(""));
+    assert!(!pda.accepts("aa"));
+}
+
+#[test]
+fn test_trace() {
+    let pda = PDA::new_from_file("0n1n.yaml").unwrap();
+    let run = pda.trace("0011").unwrap();
+
+    // the stack grows while reading 0s, then shrinks while reading 1s
+    let heights = run.iter().map(|config| config.stack.len()).collect::<Vec<usize>>();
+    assert_eq!(heights, vec![0, 1, 2, 3, 3, 2, 1, 0]);
+    assert_eq!(run[3].to_string(), "(q2, 11, $ 0 0)");
+    assert_eq!(run.last().unwrap().to_string(), "(q4, \u{3B5}, \u{3B5})");
+
+    assert_eq!(pda.trace("011"), None);
+    assert_eq!(pda.trace("").unwrap().len(), 1);
+}
+
+#[test]
+fn test_accept_by_empty_stack() {
+    // 0^n 1^n again, but with no bottom marker or final state
+    let yaml = "
+alphabet: ['0', '1']
+stack_alphabet: ['0']
+start: 1
+accept: []
+accept_by: empty_stack
+transitions:
+  - - ['0', '', '0', 1]
+    - ['', '', '', 2]
+  - - ['1', '0', '', 2]
+";
+    let pda = PDA::from_reader(yaml.as_bytes()).unwrap();
+    assert_eq!(pda.accept_by, AcceptBy::EmptyStack);
+    assert!(pda.validate().is_ok());
+    for accepted in &["", "01", "0011"] {
+        assert!(pda.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["001", "011", "10"] {
+        assert!(!pda.accepts(rejected), "should reject {:?}", rejected);
+    }
+
+    let yaml = yaml.replace("accept: []", "accept: [2]");
+    assert_eq!(PDA::from_reader(yaml.as_bytes()).unwrap().validate(),
+            Err("A PDA that accepts by empty stack cannot have final states".to_string()));
+
+    // final state is the default
+    assert_eq!(PDA::new_from_file("sample.yaml").unwrap().accept_by, AcceptBy::FinalState);
+
+    // converting to final state acceptance keeps the language
+    let converted = pda.to_final_state();
+    assert!(converted.validate().is_ok());
+    assert_eq!(converted.accept_by, AcceptBy::FinalState);
+    for input in &["", "01", "0011", "001", "011", "10", "0", "1"] {
+        assert_eq!(converted.accepts(input), pda.accepts(input), "{:?}", input);
+    }
+}
+
+#[test]
+fn test_concat_empty_stack() {
+    // 0^n 1^n by empty stack, followed by a*
+    let first = PDA::from_reader("
+alphabet: ['0', '1']
+stack_alphabet: ['0']
+start: 1
+accept: []
+accept_by: empty_stack
+transitions:
+  - - ['0', '', '0', 1]
+    - ['', '', '', 2]
+  - - ['1', '0', '', 2]
+".as_bytes()).unwrap();
+    let dfa: DFA = serde_yaml::from_str("
+alphabet: [a]
+start: 1
+accept: [1]
+transitions:
+  - [1]
+").unwrap();
+
+    let pda = first.concat(&PDA::from_dfa(&dfa));
+    assert!(pda.validate().is_ok());
+    for accepted in &["", "a", "01aa", "0011a"] {
+        assert!(pda.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["0a", "001a", "a01", "011"] {
+        assert!(!pda.accepts(rejected), "should reject {:?}", rejected);
+    }
+}
+
+#[test]
+fn test_is_deterministic() {
+    // the sample has to guess where the middle is, and so does 0n1n
+    assert!(!PDA::new_from_file("sample.yaml").unwrap().is_deterministic());
+    assert!(!PDA::new_from_file("0n1n.yaml").unwrap().is_deterministic());
+
+    // w c w^R never has a choice to make
+    let yaml = "
+alphabet: ['0', '1', c]
+stack_alphabet: ['0', '1']
+start: 1
+accept: []
+accept_by: empty_stack
+transitions:
+  - - ['0', '', '0', 1]
+    - ['1', '', '1', 1]
+    - [c, '', '', 2]
+  - - ['0', '0', '', 2]
+    - ['1', '1', '', 2]
+";
+    let pda = PDA::from_reader(yaml.as_bytes()).unwrap();
+    assert!(pda.validate().is_ok());
+    assert!(pda.is_deterministic());
+    assert!(pda.accepts("01c10"));
+
+    // popping nothing overlaps with popping anything
+    let yaml = yaml.replace("['1', '1', '', 2]", "['0', '', '', 2]");
+    assert!(!PDA::from_reader(yaml.as_bytes()).unwrap().is_deterministic());
+}
+
+#[test]
+fn test_output() {
+    let pda = PDA::new_from_file("0n1n.yaml").unwrap();
+    let path = std::env::temp_dir().join("hw5_test_output.dot");
+    let path = path.to_str().unwrap();
+    cli::write_file(path, &pda.to_graphviz()).unwrap();
+    assert_eq!(std::fs::read_to_string(path).unwrap(), pda.to_graphviz());
+    std::fs::remove_file(path).unwrap();
+}
@@ -1,32 +1,126 @@
+use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use crate::parser::AST;
 use crate::parser::Node;
-use crate::parser::CharClass;
 use crate::multimap::*;
+use serde::{Deserialize, Serialize};
 
 /// The max number of states is 2^16.
 pub type StateId = u16;
 
+/// Convert an index into a StateId, panicking if the machine has grown
+/// past the max number of states rather than wrapping around
+fn state_id(index: usize) -> StateId {
+    use std::convert::TryFrom;
+    StateId::try_from(index).unwrap_or_else(|_|
+            panic!("State {} is past the limit of {} states", index, StateId::MAX as usize + 1))
+}
+
 /// A DFA's transitions are 1 to 1. An NFA's are 1 to many.
-type DFATransitionMap = HashMap<char, StateId>;
+pub type DFATransitionMap = HashMap<char, StateId>;
 type NFATransitionMap = MultiMap<char, StateId>;
 
 /// Used internally in a few places.
 /// Should be a character that is NOT in the language.
 const DUMMY_TRANSITION: char = '_';
 
+/// NFAs with more states than this trigger a warning before determinizing
+const EXPLOSION_THRESHOLD: usize = 20;
+
 /// A deterministic finite automaton
 /// (Not technically a DFA--can have undefined transitions)
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct DFA {
     table: Vec<DFATransitionMap>,
-    accept_states: HashSet<StateId>
+    accept_states: HashSet<StateId>,
+    alphabet: HashSet<char>        // symbols declared beyond the ones in `table`
+}
+
+/// One transition taken while running a DFA on some input
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub from: StateId,
+    pub symbol: char,
+    pub to: Option<StateId>,  // None if the transition is undefined
+    pub outcome: Option<bool> // on the last step, whether the input was accepted
+}
+
+/// A record of running a DFA on some input, for tools that consume JSON
+#[derive(Serialize, Deserialize)]
+struct RunRecord {
+    input: String,
+    states: Vec<StateId>,     // every state visited, starting with 0
+    accepted: bool,
+    stuck_at: Option<usize>   // position of the symbol with no transition, if any
+}
+
+/// A DFA's reachable states and transitions, for tools that consume JSON
+#[derive(Serialize, Deserialize)]
+struct GraphRecord {
+    start: StateId,
+    accept: Vec<StateId>,
+    transitions: Vec<TransitionRecord> // sorted by source state, then symbol
+}
+
+#[derive(Serialize, Deserialize)]
+struct TransitionRecord {
+    from: StateId,
+    symbol: char,
+    to: StateId
+}
+
+/// A DFA in the YAML format read by hw2 and hw3
+#[derive(Serialize)]
+struct YamlRecord {
+    alphabet: Vec<char>,
+    start: StateId,           // states are numbered from 1
+    accept: Vec<StateId>,
+    transitions: Vec<Vec<StateId>> // one row per state, one column per symbol
+}
+
+/// How a student's DFA compares to a reference solution
+#[derive(Debug, PartialEq)]
+pub struct GradeReport {
+    pub equivalent: bool,
+    pub counterexample: Option<String>, // shortest string the two disagree on
+    pub minimal: bool,
+    pub extra_states: isize             // student's states minus the reference's
+}
+
+impl std::fmt::Display for GradeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.counterexample {
+            None => write!(f, "equivalent")?,
+            Some(string) => write!(f, "not equivalent (differs on {:?})", string)?
+        }
+        write!(f, ", {}, {:+} states", if self.minimal { "minimal" } else { "not minimal" }, self.extra_states)
+    }
+}
+
+/// A DFA whose accept states are tagged with the patterns they match
+/// When several patterns match the same string, the greatest tag (by `Ord`)
+/// has priority, so e.g. keywords can outrank identifiers by being declared
+/// after them in an enum.
+#[derive(Debug)]
+pub struct TaggedDfa<T> {
+    dfa: DFA,
+    tags: Vec<T>,                             // one per pattern
+    accept_tags: HashMap<StateId, Vec<usize>> // indices into `tags`
+}
+
+/// A DFA that can be edited while keeping its minimal form on hand
+/// Edits just mark the minimal DFA as stale, and it's recomputed the next
+/// time it's needed, so a run of edits costs a single minimization.
+pub struct MinimalDfa {
+    dfa: DFA,
+    minimal: RefCell<Option<DFA>> // None when out of date
 }
 
 /// A nondeterministic finite automaton
 /// The epsilon table holds all epsilon transitions
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct NFA {
     table: Vec<NFATransitionMap>,
     accept_state: StateId, // could be a HashSet, but our implementation only needs 1
@@ -35,9 +129,12 @@ pub struct NFA {
 
 /// Convert an AST into an NFA via a post-order traversal
 /// See `parse_nfa_node()` for the main algorithm
+/// Negated character classes are expanded against the regex character set
+/// plus the tree's working alphabet (see `AST::alphabet()`).
 pub fn ast_to_nfa(tree: &AST) -> Box<NFA> {
     let mut nfa = NFA::new();
-    nfa.accept_state = parse_nfa_node(tree.root(), nfa.add_state(), None, &mut nfa, tree);
+    let alphabet = tree.alphabet();
+    nfa.accept_state = parse_nfa_node(tree.root(), nfa.add_state(), None, &mut nfa, tree, &alphabet);
     Box::new(nfa)
 }
 
@@ -51,6 +148,154 @@ pub fn ast_to_nfa(tree: &AST) -> Box<NFA> {
 /// with the borrow checker. Also, the `composite_states` variable is conceptually
 /// a bidirectional map, but I didn't want to import an external crate just for that.
 pub fn nfa_to_dfa(nfa: &NFA) -> Box<DFA> {
+    let (dfa_states, composite_states) = subset_construction(nfa);
+
+    // Any composite state that contains the original accept state
+    // is now also an accept state
+    let mut dfa_accept_states = nfa.accept_states();
+    for (state, sub_states) in composite_states {
+        if !sub_states.is_disjoint(&dfa_accept_states) {
+            dfa_accept_states.insert(state);
+        }
+    }
+    // States only reached through epsilon transitions (e.g. the entry of a
+    // loop body) were copied over too, but nothing leads to them
+    Box::new(DFA {table: dfa_states, accept_states: dfa_accept_states, alphabet: HashSet::new()}.prune_unreachable())
+}
+
+/// Warn when an NFA is big enough that the subset construction might blow up
+/// Callers decide whether to show it, e.g. before `nfa_to_dfa()`.
+pub fn explosion_warning(nfa: &NFA) -> Option<String> {
+    let states = nfa.table.len();
+    if states > EXPLOSION_THRESHOLD {
+        Some(format!("NFA has {} states, so the DFA could have up to {} states", states, nfa.estimated_dfa_bound()))
+    } else {
+        None
+    }
+}
+
+/// Compile several regexes into a single DFA whose accept states remember
+/// which patterns they match, e.g. to build a lexer
+/// Each pattern's sub-NFA branches off a shared start state (like a big
+/// alternation), keeping track of its own accept state, and the subset
+/// construction then tags each DFA state with every pattern it contains.
+/// Fails on the first pattern that isn't a valid regex.
+pub fn multi_regex_to_dfa<T: Clone + Ord>(patterns: &[(&str, T)]) -> Result<Box<TaggedDfa<T>>, crate::RegexError> {
+    let mut nfa = NFA::new();
+    let start = nfa.add_state();
+    let mut pattern_accepts = vec![];
+    for (regex, _tag) in patterns {
+        let tree = crate::regex_to_ast(regex)?;
+
+        // same trick as alternation--keep the shared start state from being
+        // treated as a leaf, or one pattern's loop could swallow another's
+        nfa.get_mut(start).add_multi(DUMMY_TRANSITION, start);
+        pattern_accepts.push(parse_nfa_node(tree.root(), start, None, &mut nfa, &tree, &tree.alphabet()));
+    }
+    nfa.get_mut(start).remove_multi(DUMMY_TRANSITION, &start);
+
+    // Each pattern accepts in its own accept state and anything that
+    // reaches it via epsilon transitions
+    let accept_sets = pattern_accepts.iter()
+            .map(|&accept| {
+                let mut states = nfa.epsilon_table.get_multi(accept);
+                states.insert(accept);
+                states
+            })
+            .collect::<Vec<HashSet<StateId>>>();
+
+    // Tag every DFA state with the patterns whose accept states it contains
+    let (table, composite_states) = subset_construction(&nfa);
+    let mut accept_tags = HashMap::new();
+    for state in 0..table.len() as StateId {
+        let sub_states = composite_states.get(&state)
+                .cloned()
+                .unwrap_or_else(|| [state].iter().copied().collect());
+        let matching = accept_sets.iter()
+                .enumerate()
+                .filter(|(_, accepts)| !accepts.is_disjoint(&sub_states))
+                .map(|(pattern, _)| pattern)
+                .collect::<Vec<usize>>();
+        if !matching.is_empty() {
+            accept_tags.insert(state, matching);
+        }
+    }
+
+    let accept_states = accept_tags.keys().copied().collect();
+    Ok(Box::new(TaggedDfa {
+        dfa: DFA {table, accept_states, alphabet: HashSet::new()},
+        tags: patterns.iter().map(|(_regex, tag)| tag.clone()).collect(),
+        accept_tags
+    }))
+}
+
+/// Get a DFA accepting every string over `alphabet` except the given words
+/// Strings that merely start with a listed word (e.g. `cats` when `cat` is
+/// listed) are still accepted.
+pub fn dfa_rejecting_words(words: &[&str], alphabet: &HashSet<char>) -> Box<DFA> {
+    Box::new(trie(words).complement(alphabet).minimize())
+}
+
+/// Get a DFA shaped like a trie that accepts exactly the given words
+fn trie(words: &[&str]) -> DFA {
+    let mut dfa = DFA {table: vec![HashMap::new()], accept_states: HashSet::new(), alphabet: HashSet::new()};
+    for word in words {
+        let mut state = 0;
+        for letter in word.chars() {
+            state = match dfa.get(state).get(&letter) {
+                Some(&next) => next,
+                None => {
+                    let next = dfa.add_state();
+                    dfa.add_transition(state, next, letter);
+                    next
+                }
+            };
+        }
+        dfa.set_accept(state, true);
+    }
+    dfa
+}
+
+/// A list of named regexes, as loaded by `matcher_from_yaml()`
+#[derive(Deserialize)]
+struct MatcherConfig {
+    patterns: Vec<NamedPattern>
+}
+
+#[derive(Deserialize)]
+struct NamedPattern {
+    name: String,
+    regex: String
+}
+
+/// Load a list of named regexes from a YAML file and compile them into one
+/// matcher tagged with their names, e.g.
+///
+/// ```yaml
+/// patterns:
+///   - name: number
+///     regex: \d+
+/// ```
+pub fn matcher_from_yaml(path: &str) -> Result<Box<TaggedDfa<String>>, String> {
+    let file = std::fs::File::open(path)
+            .map_err(|e| format!("Unable to open `{}`: {}", path, e))?;
+    matcher_from_reader(file)
+}
+
+/// Like `matcher_from_yaml()`, but reads the YAML from any reader
+pub fn matcher_from_reader<R: std::io::Read>(reader: R) -> Result<Box<TaggedDfa<String>>, String> {
+    let config: MatcherConfig = serde_yaml::from_reader(reader)
+            .map_err(|e| format!("Unable to parse yaml: {}", e))?;
+    let patterns = config.patterns.iter()
+            .map(|pattern| (pattern.regex.as_str(), pattern.name.clone()))
+            .collect::<Vec<(&str, String)>>();
+    multi_regex_to_dfa(&patterns).map_err(|e| format!("Invalid pattern: {}", e))
+}
+
+/// Run the subset construction, returning the DFA's transition table along
+/// with the set of NFA states behind each new 'composite' state
+/// (States that aren't composite keep their NFA state number.)
+fn subset_construction(nfa: &NFA) -> (Vec<DFATransitionMap>, HashMap<StateId, HashSet<StateId>>) {
     let mut current_state: StateId = 0;
     let mut highest_state: StateId = (nfa.table.len() - 1) as StateId;
     let mut composite_states: HashMap<StateId, HashSet<StateId>> = HashMap::new(); // really should be a bidi map
@@ -90,20 +335,11 @@ pub fn nfa_to_dfa(nfa: &NFA) -> Box<DFA> {
             .collect::<HashMap<char, StateId>>());
         current_state += 1;
     }
-
-    // Any composite state that contains the original accept state
-    // is now also an accept state
-    let mut dfa_accept_states = nfa.accept_states();
-    for (state, sub_states) in composite_states {
-        if !sub_states.is_disjoint(&dfa_accept_states) {
-            dfa_accept_states.insert(state);
-        }
-    }
-    Box::new(DFA {table: dfa_states, accept_states: dfa_accept_states})
+    (dfa_states, composite_states)
 }
 
 /// Recursively traverse through the AST, adding new states to the NFA
-fn parse_nfa_node(node: &Node, input: StateId, output: Option<StateId>, nfa: &mut NFA, tree: &AST) -> StateId {
+fn parse_nfa_node(node: &Node, input: StateId, output: Option<StateId>, nfa: &mut NFA, tree: &AST, alphabet: &HashSet<char>) -> StateId {
     use Node::*;
     match *node {
         Leaf(letter) => {
@@ -112,22 +348,18 @@ fn parse_nfa_node(node: &Node, input: StateId, output: Option<StateId>, nfa: &mu
             nfa.add_transition(input, output, letter);
             output
         },
-        LeafCharClass(class) => {
+        LeafCharClass(ref class) => {
             // Character class, add 1 state with a transition for each class element
             let output = nfa.get_or_add_state(output);
-            let range = match class {
-                CharClass::AllLetter => 'a'..='z',
-                CharClass::AllDigit => '0'..='9'
-            };
-            for chr in range {
+            for chr in class.members(alphabet) {
                 nfa.add_transition(input, output, chr);
             }
             output
         },
         And(first, second) => {
             // Concatenation, connect the two subtrees sequentially
-            let intermediate = parse_nfa_node(tree.get(first), input, None, nfa, tree);
-            parse_nfa_node(tree.get(second), intermediate, output, nfa, tree)
+            let intermediate = parse_nfa_node(tree.get(first), input, None, nfa, tree, alphabet);
+            parse_nfa_node(tree.get(second), intermediate, output, nfa, tree, alphabet)
         },
         Or(choice1, choice2) => {
             // Alternation, connect the two subtrees with a branch (and rejoin them at the end)
@@ -137,35 +369,264 @@ fn parse_nfa_node(node: &Node, input: StateId, output: Option<StateId>, nfa: &mu
             // which are sometimes optimized out
             nfa.get_mut(input).add_multi(DUMMY_TRANSITION, input);
 
-            let new_output = parse_nfa_node(tree.get(choice1), input, output, nfa, tree);
-            parse_nfa_node(tree.get(choice2), input, Some(new_output), nfa, tree);
-            nfa.get_mut(input).remove(&DUMMY_TRANSITION); // remove the fake self-transition
+            let new_output = parse_nfa_node(tree.get(choice1), input, output, nfa, tree, alphabet);
+            parse_nfa_node(tree.get(choice2), input, Some(new_output), nfa, tree, alphabet);
+            nfa.get_mut(input).remove_multi(DUMMY_TRANSITION, &input); // remove the fake self-transition
             new_output
         },
         RepeatStar(body) => {
             // Kleene star, make a cycle
-            let loop_anchor = nfa.reuse_or_add_state(input);
-            parse_nfa_node(tree.get(body), loop_anchor, Some(loop_anchor), nfa, tree);
-            if let Some(output) = output {
-                nfa.add_epsilon(loop_anchor, output);
-                output
-            } else {
-                loop_anchor
-            }
+            parse_nfa_star(tree.get(body), input, output, nfa, tree, alphabet)
         },
         RepeatPlus(body) => {
             // Kleene plus, make a slightly different cycle
             let loop_anchor = nfa.reuse_or_add_state(input);
-            let loop_output = parse_nfa_node(tree.get(body), loop_anchor, None, nfa, tree);
+            let loop_output = parse_nfa_node(tree.get(body), loop_anchor, None, nfa, tree, alphabet);
             nfa.add_epsilon(loop_output, loop_anchor);
-            if let Some(output) = output {
-                nfa.add_epsilon(loop_output, output);
-                output
-            } else {
-                loop_output
+            let output = nfa.get_or_add_state(output);
+            nfa.add_epsilon(loop_output, output);
+            output
+        },
+        RepeatOptional(body) => {
+            // Optional, add a shortcut around the body
+            // The body gets its own entry state, since the shortcut makes
+            // `input` accepting and a loop in the body could anchor there.
+            let body_output = parse_nfa_fresh(tree.get(body), input, output, nfa, tree, alphabet);
+            nfa.add_epsilon(input, body_output);
+            body_output
+        },
+        RepeatRange(body, min, max) => {
+            // Bounded repetition, chain together a copy of the body for each
+            // mandatory repetition, then either a shortcut around each optional
+            // copy or a cycle if there's no upper bound
+            // Every copy starts from its own entry state so a loop in one copy
+            // can't be entered from the end of the previous one.
+            let body = tree.get(body);
+            let optional = max.map(|max| max - min);
+            let mut current = input;
+            for i in 0..min {
+                let last = i + 1 == min && optional == Some(0);
+                current = parse_nfa_fresh(body, current, if last { output } else { None }, nfa, tree, alphabet);
+            }
+            match optional {
+                None => parse_nfa_star(body, current, output, nfa, tree, alphabet),
+                Some(0) if min == 0 => {
+                    // {0} only matches the empty string
+                    let output = nfa.get_or_add_state(output);
+                    nfa.add_epsilon(input, output);
+                    output
+                },
+                Some(0) => current,
+                Some(optional) => {
+                    let mut skipped = vec![];
+                    for i in 0..optional {
+                        skipped.push(current);
+                        let last = i + 1 == optional;
+                        current = parse_nfa_fresh(body, current, if last { output } else { None }, nfa, tree, alphabet);
+                    }
+                    for state in skipped {
+                        nfa.add_epsilon(state, current);
+                    }
+                    current
+                }
+            }
+        }
+    }
+}
+
+/// Add a Kleene star cycle around `body`, starting at `input`
+/// The loop anchor accepts, so the body starts from a fresh state rather
+/// than letting a loop inside it anchor there too.
+fn parse_nfa_star(body: &Node, input: StateId, output: Option<StateId>, nfa: &mut NFA, tree: &AST, alphabet: &HashSet<char>) -> StateId {
+    let loop_anchor = nfa.reuse_or_add_state(input);
+    parse_nfa_fresh(body, loop_anchor, Some(loop_anchor), nfa, tree, alphabet);
+    let output = nfa.get_or_add_state(output);
+    nfa.add_epsilon(loop_anchor, output);
+    output
+}
+
+/// Parse `body` starting from a new state, reached from `input` by an
+/// epsilon transition
+fn parse_nfa_fresh(body: &Node, input: StateId, output: Option<StateId>, nfa: &mut NFA, tree: &AST, alphabet: &HashSet<char>) -> StateId {
+    let entry = nfa.add_state();
+    nfa.add_epsilon(input, entry);
+    parse_nfa_node(body, entry, output, nfa, tree, alphabet)
+}
+
+impl<T: Clone + Ord> TaggedDfa<T> {
+    /// Find the longest prefix of `input` that some pattern matches (maximal munch)
+    /// Returns the length of the match in bytes along with the highest-priority
+    /// tag that matches it. A pattern that accepts the empty string can
+    /// produce a match of length 0.
+    pub fn longest_match(&self, input: &str) -> Option<(usize, T)> {
+        let mut state = 0;
+        let mut longest = self.best_tag(state).map(|tag| (0, tag));
+        for (i, letter) in input.char_indices() {
+            match self.dfa.get(state).get(&letter) {
+                None => break,
+                Some(&next_state) => state = next_state
+            }
+            if let Some(tag) = self.best_tag(state) {
+                longest = Some((i + letter.len_utf8(), tag));
+            }
+        }
+        longest
+    }
+
+    /// Get the tags of every pattern that matches all of `input`, in the
+    /// order the patterns were given
+    pub fn all_matches(&self, input: &str) -> Vec<T> {
+        let mut state = 0;
+        for letter in input.chars() {
+            match self.dfa.get(state).get(&letter) {
+                None => return vec![],
+                Some(&next_state) => state = next_state
             }
         }
+        self.accept_tags.get(&state)
+                .map(|patterns| patterns.iter().map(|&i| self.tags[i].clone()).collect())
+                .unwrap_or_default()
+    }
+
+    /// Get the highest-priority tag for a state, if it accepts at all
+    fn best_tag(&self, state: StateId) -> Option<T> {
+        self.accept_tags.get(&state)
+                .and_then(|patterns| patterns.iter().map(|&i| &self.tags[i]).max())
+                .cloned()
+    }
+}
+
+impl MinimalDfa {
+    /// Wrap a DFA for editing
+    pub fn new(dfa: DFA) -> MinimalDfa {
+        MinimalDfa {dfa, minimal: RefCell::new(None)}
+    }
+
+    /// Add a new state with no transitions and return its index
+    pub fn add_state(&mut self) -> StateId {
+        self.minimal.replace(None);
+        self.dfa.add_state()
+    }
+
+    /// Add a transition, replacing any existing one on the same symbol
+    pub fn add_transition(&mut self, from: StateId, to: StateId, label: char) {
+        self.minimal.replace(None);
+        self.dfa.add_transition(from, to, label);
+    }
+
+    /// Make a state accepting or not
+    pub fn set_accept(&mut self, state: StateId, accept: bool) {
+        self.minimal.replace(None);
+        self.dfa.set_accept(state, accept);
+    }
+
+    /// Get the minimal DFA for the current machine, minimizing it if needed
+    pub fn minimal(&self) -> Ref<'_, DFA> {
+        if self.minimal.borrow().is_none() {
+            self.minimal.replace(Some(self.dfa.minimize()));
+        }
+        Ref::map(self.minimal.borrow(), |minimal| minimal.as_ref().unwrap())
+    }
+
+    /// Check whether a string is accepted
+    pub fn accepts(&self, input: &str) -> bool {
+        self.minimal().accepts(input)
+    }
+
+    /// Get the number of states in the minimal DFA
+    pub fn state_count(&self) -> usize {
+        self.minimal().num_states()
+    }
+}
+
+/// Where each state of a DFA ends up after reading some string, or None if
+/// the string runs into an undefined transition
+type Transformation = Vec<Option<StateId>>;
+
+/// Apply one transformation, then another
+fn compose(first: &Transformation, second: &Transformation) -> Transformation {
+    first.iter()
+            .map(|state| state.and_then(|state| second[state as usize]))
+            .collect()
+}
+
+/// An edge label in a generalized NFA, used to turn a DFA back into a regex
+#[derive(Clone, PartialEq)]
+enum EdgeRegex {
+    Epsilon,
+    Regex(String, Precedence)
+}
+
+/// How loosely a regex binds, from loosest to tightest, to decide where
+/// parentheses are needed
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Union, Concat, Atom
+}
+
+impl EdgeRegex {
+    /// A regex matching any one of the given symbols
+    fn symbols(symbols: &HashSet<char>) -> EdgeRegex {
+        if symbols.iter().any(|&symbol| crate::lexer::is_metachar(symbol)) {
+            let mut symbols = symbols.iter().copied().collect::<Vec<char>>();
+            symbols.sort_unstable();
+            symbols.into_iter()
+                    .map(|symbol| {
+                        let escaped = format!("\\{}", symbol);
+                        let symbol = if crate::lexer::is_metachar(symbol) { escaped } else { symbol.to_string() };
+                        EdgeRegex::Regex(symbol, Precedence::Atom)
+                    })
+                    .reduce(|a, b| a.union(&b))
+                    .unwrap()
+        } else {
+            EdgeRegex::Regex(class_label(symbols), Precedence::Atom)
+        }
+    }
+
+    /// The regex as a string, parenthesized if it binds more loosely than `min`
+    fn wrap(&self, min: Precedence) -> String {
+        match self {
+            EdgeRegex::Epsilon => String::new(),
+            EdgeRegex::Regex(regex, precedence) if *precedence < min => format!("({})", regex),
+            EdgeRegex::Regex(regex, _) => regex.clone()
+        }
+    }
+
+    fn union(&self, other: &EdgeRegex) -> EdgeRegex {
+        use EdgeRegex::*;
+        match (self, other) {
+            _ if self == other => self.clone(),
+            (Epsilon, regex) | (regex, Epsilon) => Regex(regex.wrap(Precedence::Atom) + "?", Precedence::Concat),
+            _ => Regex(format!("{}|{}", self.wrap(Precedence::Union), other.wrap(Precedence::Union)), Precedence::Union)
+        }
+    }
+
+    fn concat(&self, other: &EdgeRegex) -> EdgeRegex {
+        use EdgeRegex::*;
+        match (self, other) {
+            (Epsilon, regex) | (regex, Epsilon) => regex.clone(),
+            _ => Regex(self.wrap(Precedence::Concat) + &other.wrap(Precedence::Concat), Precedence::Concat)
+        }
+    }
+
+    fn star(&self) -> EdgeRegex {
+        match self {
+            EdgeRegex::Epsilon => EdgeRegex::Epsilon,
+            regex => EdgeRegex::Regex(regex.wrap(Precedence::Atom) + "*", Precedence::Concat)
+        }
+    }
+}
+
+/// Format a symbol class as a label, e.g. `a` or `[0-9a-c]`
+/// Runs of three or more consecutive characters are shortened to a range.
+fn class_label(class: &HashSet<char>) -> String {
+    let mut symbols = class.iter().copied().collect::<Vec<char>>();
+    symbols.sort_unstable();
+    if symbols.len() == 1 {
+        return symbols[0].to_string();
     }
+
+    format!("[{}]", crate::lexer::ranges(&symbols).concat())
 }
 
 /// Find the first key that maps to the given value
@@ -180,17 +641,13 @@ fn find_key_by_value<K, V>(map: &HashMap<K, V>, value: &V) -> Option<K> where
 impl NFA {
     /// Make a new, empty NFA
     pub fn new() -> NFA {
-        NFA {
-            table: vec![],
-            accept_state: 0,
-            epsilon_table: HashMap::new()
-        }
+        NFA::default()
     }
 
     /// Add a new state to the NFA and return its index
     pub fn add_state(&mut self) -> StateId {
         self.table.push(HashMap::new());
-        (self.table.len() - 1) as StateId
+        state_id(self.table.len() - 1)
     }
 
     /// If the given state exists, return it; otherwise make a new one
@@ -213,20 +670,30 @@ impl NFA {
     /// Add a labeled transition between two states
     pub fn add_transition(&mut self, from: StateId, to: StateId, label: char) {
         self.get_mut(from).add_multi(label, to);
-        for state in self.epsilon_table.get_multi(from) {
-            self.get_mut(state).add_multi(label, to);
+        for &state in self.epsilon_table.get_multi_ref(from).into_iter().flatten() {
+            self.table[state as usize].add_multi(label, to);
         }
     }
 
     /// Add an epsilon transition between two states
+    /// The epsilon table is kept transitively closed: everything that reaches
+    /// `from` by epsilons now also reaches `to` and whatever `to` reaches.
     pub fn add_epsilon(&mut self, from: StateId, to: StateId) {
-        self.epsilon_table.add_multi(to, from);
-        for state in self.epsilon_table.get_multi(from) {
-            self.epsilon_table.add_multi(to, state);
+        let mut sources = self.epsilon_table.get_multi(from);
+        sources.insert(from);
+        let mut targets = self.epsilon_table.iter()
+                .filter(|(_, reached_from)| reached_from.contains(&to))
+                .map(|(&target, _)| target)
+                .collect::<HashSet<StateId>>();
+        targets.insert(to);
+        for &target in &targets {
+            self.epsilon_table.add_all_multi(target, &sources);
         }
+
+        // `to` already has the transitions of every state it reaches
         for (label, states) in self.get(to).clone() {
-            for epsilon_state in self.epsilon_table.get_multi(to) {
-                self.get_mut(epsilon_state).add_all_multi(label, &states);
+            for &source in &sources {
+                self.table[source as usize].add_all_multi(label, &states);
             }
         }
     }
@@ -253,8 +720,96 @@ impl NFA {
         self.get(state).is_empty()
     }
 
+    /// Get an upper bound on the number of states the equivalent DFA can have
+    /// (one per subset of NFA states), saturating at `usize::MAX`
+    pub fn estimated_dfa_bound(&self) -> usize {
+        let states = self.table.len();
+        if states >= usize::BITS as usize {
+            usize::MAX
+        } else {
+            1 << states
+        }
+    }
+
+    /// Convert this NFA to a DFA, same as `nfa_to_dfa(self)`
+    ///
+    /// ```
+    /// use csis_regex::{automata::ast_to_nfa, lexer, parser};
+    ///
+    /// let tokens = lexer::tokenize("(ab)*").unwrap();
+    /// let mut tree = parser::tree();
+    /// parser::parse(&tokens, &mut tree).unwrap();
+    ///
+    /// let dfa = ast_to_nfa(&tree).determinize().minimize();
+    /// assert!(dfa.accepts("abab"));
+    /// ```
+    pub fn determinize(&self) -> Box<DFA> {
+        nfa_to_dfa(self)
+    }
+
+    /// Check whether the NFA accepts `input` by simulating it directly,
+    /// tracking every state it could be in, rather than converting it to a DFA
+    pub fn accepts(&self, input: &str) -> bool {
+        let mut current = self.epsilon_closure(&[0].iter().copied().collect());
+        for letter in input.chars() {
+            let next = current.iter()
+                    .flat_map(|&state| self.get(state).get_multi_ref(letter).into_iter().flatten().copied())
+                    .collect();
+            current = self.epsilon_closure(&next);
+            if current.is_empty() {
+                return false;
+            }
+        }
+        current.contains(&self.accept_state)
+    }
+
+    /// Get every state reachable from `states` through zero or more epsilon
+    /// transitions (so the result always includes `states` themselves)
+    /// Labeled transitions are already copied back across epsilon transitions
+    /// as they're added (which is all `nfa_to_dfa()` relies on), so this only
+    /// matters for reaching accept states and for seeing the whole picture.
+    pub fn epsilon_closure(&self, states: &HashSet<StateId>) -> HashSet<StateId> {
+        let mut closure = states.clone();
+        loop {
+            // the epsilon table maps each state to the states with an epsilon into it
+            let reached = self.epsilon_table.iter()
+                    .filter(|(to, from)| !closure.contains(to) && !from.is_disjoint(&closure))
+                    .map(|(&to, _)| to)
+                    .collect::<Vec<StateId>>();
+            if reached.is_empty() {
+                return closure;
+            }
+            closure.extend(reached);
+        }
+    }
+
+    /// Simulate the NFA on `input` and find the first point where a single
+    /// state and symbol lead to more than one successor
+    /// Returns the position of the symbol in the input (counted in chars), the
+    /// branching state, and its successors in ascending order. Active states
+    /// are checked in ascending order too, so the result is deterministic.
+    pub fn first_branch_point(&self, input: &str) -> Option<(usize, StateId, Vec<StateId>)> {
+        let mut current: HashSet<StateId> = [0].iter().copied().collect();
+        for (position, letter) in input.chars().enumerate() {
+            let mut states = current.into_iter().collect::<Vec<StateId>>();
+            states.sort_unstable();
+
+            let mut next = HashSet::new();
+            for state in states {
+                let targets = self.get(state).get_multi(letter);
+                if targets.len() > 1 {
+                    let mut targets = targets.into_iter().collect::<Vec<StateId>>();
+                    targets.sort_unstable();
+                    return Some((position, state, targets));
+                }
+                next.extend(targets);
+            }
+            current = next;
+        }
+        None
+    }
+
     /// Get the GraphViz representation of this NFA
-    #[allow(dead_code)]
     pub fn to_graph(&self) -> String {
         crate::graphviz::generate(0, &self.accept_states(), &self.edges())
     }
@@ -270,15 +825,85 @@ impl NFA {
 }
 
 impl DFA {
+    /// Get a DFA accepting every string over `alphabet`
+    pub fn universal(alphabet: &HashSet<char>) -> Box<DFA> {
+        Box::new(DFA {
+            table: vec![alphabet.iter().map(|&symbol| (symbol, 0)).collect()],
+            accept_states: [0].iter().copied().collect(),
+            alphabet: alphabet.clone()
+        })
+    }
+
+    /// Get a DFA over `alphabet` that accepts nothing
+    pub fn empty_language(alphabet: &HashSet<char>) -> Box<DFA> {
+        Box::new(DFA {
+            table: vec![HashMap::new()],
+            accept_states: HashSet::new(),
+            alphabet: alphabet.clone()
+        })
+    }
+
     /// Get a state's transition map immutably
     pub fn get(&self, state: StateId) -> &DFATransitionMap {
-        return self.table.get(state as usize).unwrap();
+        self.table.get(state as usize).unwrap()
+    }
+
+    /// Add a new state with no transitions and return its index
+    pub fn add_state(&mut self) -> StateId {
+        self.table.push(HashMap::new());
+        state_id(self.table.len() - 1)
+    }
+
+    /// Add a transition, replacing any existing one on the same symbol
+    pub fn add_transition(&mut self, from: StateId, to: StateId, label: char) {
+        self.table[from as usize].insert(label, to);
+    }
+
+    /// Make a state accepting or not
+    pub fn set_accept(&mut self, state: StateId, accept: bool) {
+        if accept {
+            self.accept_states.insert(state);
+        } else {
+            self.accept_states.remove(&state);
+        }
+    }
+
+    /// Attach an alphabet to this DFA, e.g. one built by hand, so that its
+    /// symbols are known even where no transition uses them
+    /// Fails if some transition is labeled with a symbol outside the alphabet.
+    pub fn with_alphabet(mut self, alphabet: HashSet<char>) -> Result<DFA, String> {
+        for (state, transitions) in self.table.iter().enumerate() {
+            if let Some(label) = transitions.keys().find(|label| !alphabet.contains(label)) {
+                return Err(format!("State {} has a transition on `{}`, which is not in the alphabet", state, label));
+            }
+        }
+        self.alphabet = alphabet;
+        Ok(self)
+    }
+
+    /// Get this DFA's alphabet: every symbol it was given with `with_alphabet()`,
+    /// plus every symbol that labels a transition
+    pub fn alphabet(&self) -> HashSet<char> {
+        let mut alphabet = self.alphabet.clone();
+        alphabet.extend(self.table.iter().flat_map(|trans| trans.keys().copied()));
+        alphabet
+    }
+
+    /// Get the number of states in this DFA, reachable or not
+    pub fn num_states(&self) -> usize {
+        self.table.len()
     }
 
     /// Check whether a string is accepted by this DFA
     pub fn accepts(&self, input: &str) -> bool {
+        self.accepts_with_fold(input, |letter| letter)
+    }
+
+    /// Check whether a string is accepted after passing each character through
+    /// `fold`, e.g. `char::to_ascii_lowercase` to ignore case
+    pub fn accepts_with_fold(&self, input: &str, fold: impl Fn(char) -> char) -> bool {
         let mut state = 0;
-        for letter in input.chars() {
+        for letter in input.chars().map(fold) {
             match self.get(state).get(&letter) {
                 None => return false,                    // reject if no transition defined
                 Some(&next_state) => state = next_state  // otherwise move to next state
@@ -287,32 +912,2387 @@ impl DFA {
         self.accept_states.contains(&state)
     }
 
-    /// Get the GraphViz representation of this DFA
-    pub fn to_graph(&self) -> String {
-        crate::graphviz::generate(0, &self.accept_states, &self.edges())
+    /// Run the DFA on `input`, recording every transition taken
+    /// The run stops early at an undefined transition, which rejects. The
+    /// empty string takes no steps, so check `accepts("")` instead.
+    pub fn run_steps(&self, input: &str) -> Vec<Step> {
+        let mut steps = vec![];
+        let mut state = 0;
+        for symbol in input.chars() {
+            let next_state = self.get(state).get(&symbol).copied();
+            steps.push(Step {from: state, symbol, to: next_state, outcome: None});
+            match next_state {
+                None => break,
+                Some(next_state) => state = next_state
+            }
+        }
+        if let Some(last) = steps.last_mut() {
+            last.outcome = Some(last.to.is_some_and(|state| self.accept_states.contains(&state)));
+        }
+        steps
     }
 
-    fn edges(&self) -> Vec<crate::graphviz::Edge> {
-        let reachable = self.get_reachable();
-        self.table.iter()
-                .enumerate()
-                .flat_map(|(s, trans)| trans.iter()
-                    .map(move |(label, dest)| (s as StateId, *dest, *label)))
-                .filter(|(from, to, _label)| reachable.contains(from) && reachable.contains(to))
+    /// Run the DFA on `input`, listing each transition taken as
+    /// `(state, symbol, next state)`
+    /// The next state is None where there's no transition for the symbol, in
+    /// which case the input is rejected and the trace ends there.
+    pub fn trace(&self, input: &str) -> Vec<(StateId, char, Option<StateId>)> {
+        self.run_steps(input).into_iter()
+                .map(|step| (step.from, step.symbol, step.to))
                 .collect()
     }
 
-    fn get_reachable(&self) -> HashSet<StateId> {
-        let mut reachable = HashSet::new();
-        self.visit_reachable(0, &mut reachable);
-        reachable
+    /// Count the strings of exactly length `n` this DFA accepts
+    /// Rather than listing them, this tracks how many strings of each length
+    /// end in each state, so it runs in O(n * transitions). Counts saturate at
+    /// `u64::MAX` instead of overflowing.
+    pub fn count_accepted(&self, n: usize) -> u64 {
+        let mut counts = vec![0u64; self.table.len()];
+        counts[0] = 1;
+        for _ in 0..n {
+            let mut next = vec![0u64; self.table.len()];
+            for (state, transitions) in self.table.iter().enumerate() {
+                for &to in transitions.values() {
+                    next[to as usize] = next[to as usize].saturating_add(counts[state]);
+                }
+            }
+            counts = next;
+        }
+        self.accept_states.iter()
+                .fold(0u64, |total, &state| total.saturating_add(counts[state as usize]))
+    }
+
+    /// Run the DFA on `input` and describe the run as a JSON object with the
+    /// input, the states visited, whether it was accepted, and the position
+    /// (in chars) where it got stuck on an undefined transition, if it did
+    pub fn run_to_json(&self, input: &str) -> String {
+        let steps = self.run_steps(input);
+        let mut states = vec![0];
+        states.extend(steps.iter().filter_map(|step| step.to));
+        let record = RunRecord {
+            input: input.to_string(),
+            states,
+            accepted: self.accepts(input),
+            stuck_at: steps.iter().position(|step| step.to.is_none())
+        };
+        serde_json::to_string(&record).unwrap()
     }
 
-    fn visit_reachable(&self, state: StateId, reachable: &mut HashSet<StateId>) {
-        if reachable.insert(state) {
-            for (_label, neighbor) in self.get(state) {
-                self.visit_reachable(*neighbor, reachable);
+    /// Describe this DFA in the YAML format read by hw2 and hw3, with the
+    /// alphabet in sorted order and states numbered from 1
+    /// The YAML format needs a transition on every symbol, so undefined
+    /// transitions are sent to a trap state first (see `complete()`).
+    pub fn to_yaml(&self) -> String {
+        let mut alphabet = self.alphabet().into_iter().collect::<Vec<char>>();
+        alphabet.sort_unstable();
+        let mut complete = self.clone();
+        complete.complete(&alphabet.iter().copied().collect());
+
+        let mut accept = complete.accept_states.iter()
+                .map(|state| state + 1)
+                .collect::<Vec<StateId>>();
+        accept.sort_unstable();
+        let record = YamlRecord {
+            start: 1,
+            accept,
+            transitions: complete.table.iter()
+                    .map(|trans| alphabet.iter().map(|symbol| trans[symbol] + 1).collect())
+                    .collect(),
+            alphabet
+        };
+        serde_yaml::to_string(&record).unwrap()
+    }
+
+    /// Check whether this DFA accepts the empty string
+    pub fn accepts_empty(&self) -> bool {
+        self.accept_states.contains(&0)
+    }
+
+    /// Check whether some scattered subsequence of `input` (its characters in
+    /// order, but not necessarily next to each other) is accepted
+    pub fn accepts_as_subsequence(&self, input: &str) -> bool {
+        // every state reachable by keeping or skipping each character so far
+        let mut states: HashSet<StateId> = [0].iter().copied().collect();
+        for letter in input.chars() {
+            let kept = states.iter()
+                    .filter_map(|&state| self.get(state).get(&letter).copied())
+                    .collect::<Vec<StateId>>();
+            states.extend(kept);
+        }
+        !states.is_disjoint(&self.accept_states)
+    }
+
+    /// Check whether this DFA accepts at least one of the given strings,
+    /// stopping at the first one it accepts
+    pub fn accepts_any_of(&self, inputs: &[&str]) -> bool {
+        self.first_accepted(inputs).is_some()
+    }
+
+    /// Get the index of the first of the given strings this DFA accepts,
+    /// without running the ones after it
+    fn first_accepted(&self, inputs: &[&str]) -> Option<usize> {
+        inputs.iter().position(|input| self.accepts(input))
+    }
+
+    /// Split a list of strings into the ones this DFA accepts and the ones it rejects
+    pub fn partition_inputs(&self, inputs: &[&str]) -> (Vec<String>, Vec<String>) {
+        let (accepted, rejected): (Vec<&str>, Vec<&str>) = inputs.iter()
+                .partition(|input| self.accepts(input));
+        (accepted.into_iter().map(String::from).collect(),
+         rejected.into_iter().map(String::from).collect())
+    }
+
+    /// Check whether the reverse of a string is accepted by this DFA
+    /// Instead of building the reverse automaton, this runs the transitions
+    /// backwards: starting from the accept states, step to the predecessors
+    /// on each letter of `input`, then check whether the start state is
+    /// among the states we end up in.
+    pub fn accepts_reversed(&self, input: &str) -> bool {
+        let mut states = self.accept_states.clone();
+        for letter in input.chars() {
+            states = self.table.iter()
+                    .enumerate()
+                    .filter(|(_, trans)| trans.get(&letter).is_some_and(|next| states.contains(next)))
+                    .map(|(state, _)| state as StateId)
+                    .collect();
+        }
+        states.contains(&0)
+    }
+
+    /// Check whether this DFA and another accept some string in common,
+    /// using only symbols from the given alphabet
+    /// This walks the product of the two machines one pair of states at a
+    /// time and stops as soon as both sides accept, so the full product
+    /// automaton is never built.
+    pub fn intersects(&self, other: &DFA, alphabet: &HashSet<char>) -> bool {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert((0, 0));
+        queue.push_back((0, 0));
+
+        while let Some((state, other_state)) = queue.pop_front() {
+            if self.accept_states.contains(&state) && other.accept_states.contains(&other_state) {
+                return true;
+            }
+
+            // a pair only has a transition if both machines define one
+            for (label, &next) in self.get(state) {
+                if !alphabet.contains(label) { continue }
+                if let Some(&other_next) = other.get(other_state).get(label) {
+                    if visited.insert((next, other_next)) {
+                        queue.push_back((next, other_next));
+                    }
+                }
             }
         }
+        false
     }
-}
\ No newline at end of file
+
+    /// Get a DFA accepting the strings this one accepts that also contain an
+    /// even (or odd) number of `symbol`
+    pub fn with_parity_constraint(&self, symbol: char, even: bool) -> Box<DFA> {
+        // two states, flipping on `symbol` and staying put on anything else
+        let mut alphabet = self.alphabet();
+        alphabet.insert(symbol);
+        let parity = DFA {
+            table: (0..2)
+                    .map(|state| alphabet.iter()
+                        .map(|&other| (other, if other == symbol { 1 - state } else { state }))
+                        .collect())
+                    .collect(),
+            accept_states: [if even { 0 } else { 1 }].iter().copied().collect(),
+            alphabet: HashSet::new()
+        };
+        Box::new(self.product(&parity, |a, b| a && b))
+    }
+
+    /// Run this DFA and another side by side, accepting when `accept` says
+    /// so given whether each of them accepts
+    /// An undefined transition in one DFA just means that side has rejected
+    /// for good; the other side keeps running, unless `accept` can't be
+    /// satisfied without that side, in which case the transition is left out.
+    fn product(&self, other: &DFA, accept: impl Fn(bool, bool) -> bool) -> DFA {
+        let mut alphabet = self.alphabet();
+        alphabet.extend(other.alphabet());
+        let mut symbols = alphabet.iter().copied().collect::<Vec<char>>();
+        symbols.sort_unstable();
+        let step = |dfa: &DFA, state: Option<StateId>, symbol| state.and_then(|state| dfa.get(state).get(&symbol).copied());
+        let accepts = |dfa: &DFA, state: Option<StateId>| state.is_some_and(|state| dfa.accept_states.contains(&state));
+        let needs_self = !accept(false, false) && !accept(false, true);
+        let needs_other = !accept(false, false) && !accept(true, false);
+
+        // number the pairs of states in the order we find them
+        let start = (Some(0), Some(0));
+        let mut ids = HashMap::new();
+        let mut pairs = vec![start];
+        ids.insert(start, 0);
+        let mut table = vec![];
+        let mut accept_states = HashSet::new();
+        while table.len() < pairs.len() {
+            let (a, b) = pairs[table.len()];
+            if accept(accepts(self, a), accepts(other, b)) {
+                accept_states.insert(table.len() as StateId);
+            }
+            let mut transitions = HashMap::new();
+            for &symbol in &symbols {
+                let next = (step(self, a, symbol), step(other, b, symbol));
+                if next == (None, None) || (needs_self && next.0.is_none()) || (needs_other && next.1.is_none()) {
+                    continue;
+                }
+                let id = *ids.entry(next).or_insert_with(|| {
+                    pairs.push(next);
+                    (pairs.len() - 1) as StateId
+                });
+                transitions.insert(symbol, id);
+            }
+            table.push(transitions);
+        }
+        DFA {table, accept_states, alphabet}
+    }
+
+    /// Get a DFA accepting the strings both this DFA and `other` accept
+    /// States are pairs of states from the two DFAs, and a transition only
+    /// exists where both of them have one.
+    pub fn intersect(&self, other: &DFA) -> DFA {
+        self.product(other, |a, b| a && b)
+    }
+
+    /// Get a DFA accepting the strings either this DFA or `other` accepts
+    /// Where only one of them has a transition, the product keeps running
+    /// that one, treating the other as stuck in a dead state.
+    pub fn union(&self, other: &DFA) -> DFA {
+        self.product(other, |a, b| a || b)
+    }
+
+    /// Get a DFA accepting the strings this DFA accepts but `other` doesn't
+    /// `other` is completed over both DFAs' alphabets before taking its
+    /// complement, so strings it rejects by running off a missing transition
+    /// still count.
+    pub fn difference(&self, other: &DFA) -> DFA {
+        let mut alphabet = self.alphabet();
+        alphabet.extend(other.alphabet());
+        self.intersect(&other.complement(&alphabet))
+    }
+
+    /// Check whether this DFA and `other` accept exactly the same strings,
+    /// i.e. whether their symmetric difference accepts nothing
+    pub fn equivalent(&self, other: &DFA) -> bool {
+        // the product construction only builds reachable states
+        self.difference(other).union(&other.difference(self)).accept_states.is_empty()
+    }
+
+    /// Grade this DFA against a reference DFA for the intended language,
+    /// checking strings over `alphabet`
+    pub fn grade_against(&self, reference: &DFA, alphabet: &HashSet<char>) -> GradeReport {
+        let counterexample = self.product(reference, |a, b| a != b).shortest_accepted_over(alphabet);
+
+        // `minimize()` drops trap states, so compare complete machines, or
+        // any DFA with a trap state would look too big
+        let complete_size = |dfa: &DFA| {
+            let mut dfa = dfa.clone();
+            dfa.complete(alphabet);
+            dfa.num_states()
+        };
+        GradeReport {
+            equivalent: counterexample.is_none(),
+            counterexample,
+            minimal: complete_size(&self.minimize()) == complete_size(self),
+            extra_states: self.num_states() as isize - reference.num_states() as isize
+        }
+    }
+
+    /// Check whether this DFA accepts nothing at all, i.e. whether none of
+    /// its accept states can be reached from the start
+    pub fn is_empty(&self) -> bool {
+        self.get_reachable().is_disjoint(&self.accept_states)
+    }
+
+    /// Check whether this DFA accepts only finitely many strings
+    /// The language is infinite exactly when some cycle runs through states
+    /// that are reachable from the start and can reach an accept state, so
+    /// this tries to topologically sort those states. Anything left over once
+    /// no more states can be peeled off is part of a cycle.
+    pub fn is_finite(&self) -> bool {
+        let useful = self.get_reachable()
+                .intersection(&self.get_coaccessible())
+                .copied()
+                .collect::<HashSet<StateId>>();
+        let successors = |state: StateId| self.get(state).values().copied().filter(|next| useful.contains(next));
+
+        let mut in_degree: HashMap<StateId, usize> = useful.iter().map(|&state| (state, 0)).collect();
+        for &state in &useful {
+            for next in successors(state) {
+                *in_degree.get_mut(&next).unwrap() += 1;
+            }
+        }
+        let mut ready = in_degree.iter()
+                .filter(|(_, &degree)| degree == 0)
+                .map(|(&state, _)| state)
+                .collect::<Vec<StateId>>();
+        let mut peeled = 0;
+        while let Some(state) = ready.pop() {
+            peeled += 1;
+            for next in successors(state) {
+                let degree = in_degree.get_mut(&next).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push(next);
+                }
+            }
+        }
+        peeled == useful.len()
+    }
+
+    /// Generate a random string of at most `max_len` chars that this DFA
+    /// accepts, or None if it doesn't accept any strings that short
+    /// This is a random walk from the start state that only takes transitions
+    /// that can still reach an accept state within the remaining length, and
+    /// at each accept state along the way, stopping is one more option to
+    /// choose from. Only available with the `rand` feature.
+    #[cfg(feature = "rand")]
+    pub fn sample(&self, rng: &mut impl rand::Rng, max_len: usize) -> Option<String> {
+        // how far each state is from the nearest accept state
+        let mut distance: HashMap<StateId, usize> = self.accept_states.iter().map(|&state| (state, 0)).collect();
+        let mut queue = self.accept_states.iter().copied().collect::<VecDeque<StateId>>();
+        while let Some(state) = queue.pop_front() {
+            for (from, transitions) in self.table.iter().enumerate() {
+                let from = from as StateId;
+                if !distance.contains_key(&from) && transitions.values().any(|&to| to == state) {
+                    distance.insert(from, distance[&state] + 1);
+                    queue.push_back(from);
+                }
+            }
+        }
+        if *distance.get(&0)? > max_len {
+            return None;
+        }
+
+        let mut string = String::new();
+        let mut state = 0;
+        for remaining in (0..max_len).rev() {
+            let mut options = self.get(state).iter()
+                    .filter(|(_, next)| distance.get(next).is_some_and(|&distance| distance <= remaining))
+                    .map(|(&symbol, &next)| Some((symbol, next)))
+                    .collect::<Vec<Option<(char, StateId)>>>();
+            options.sort_unstable();
+            if self.accept_states.contains(&state) {
+                options.push(None);
+            }
+            match options[rng.gen_range(0..options.len())] {
+                None => return Some(string),
+                Some((symbol, next)) => {
+                    string.push(symbol);
+                    state = next;
+                }
+            }
+        }
+        Some(string)
+    }
+
+    /// Find the shortest string this DFA accepts (ties broken alphabetically),
+    /// or None if its language is empty
+    pub fn shortest_accepted(&self) -> Option<String> {
+        self.shortest_accepted_over(&self.alphabet())
+    }
+
+    /// Find the shortest string over `alphabet` this DFA accepts (ties broken
+    /// alphabetically), if there is one
+    fn shortest_accepted_over(&self, alphabet: &HashSet<char>) -> Option<String> {
+        let mut symbols = alphabet.iter().copied().collect::<Vec<char>>();
+        symbols.sort_unstable();
+        let mut visited: HashSet<StateId> = [0].iter().copied().collect();
+        let mut queue: VecDeque<(StateId, String)> = [(0, String::new())].iter().cloned().collect();
+        while let Some((state, string)) = queue.pop_front() {
+            if self.accept_states.contains(&state) {
+                return Some(string);
+            }
+            for symbol in &symbols {
+                if let Some(&next) = self.get(state).get(symbol) {
+                    if visited.insert(next) {
+                        queue.push_back((next, format!("{}{}", string, symbol)));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Find the shortest string (over `alphabet`) that this DFA accepts when
+    /// started from one of `s` and `t` but not the other, or None if the two
+    /// states are equivalent
+    /// Ties are broken alphabetically. An undefined transition counts as
+    /// going to a dead state that rejects everything.
+    pub fn separating_sequence(&self, s: StateId, t: StateId, alphabet: &HashSet<char>) -> Option<String> {
+        let mut symbols = alphabet.iter().copied().collect::<Vec<char>>();
+        symbols.sort_unstable();
+        let accepts = |state: Option<StateId>| state.is_some_and(|state| self.accept_states.contains(&state));
+        let step = |state: Option<StateId>, symbol| state.and_then(|state| self.get(state).get(&symbol).copied());
+
+        // breadth-first search over pairs of states, remembering how we got to each
+        let start = (Some(s), Some(t));
+        let mut parents = HashMap::new();
+        let mut queue = VecDeque::new();
+        parents.insert(start, None);
+        queue.push_back(start);
+
+        while let Some(pair) = queue.pop_front() {
+            if accepts(pair.0) != accepts(pair.1) {
+                let mut sequence = vec![];
+                let mut current = pair;
+                while let Some(&Some((previous, symbol))) = parents.get(&current) {
+                    sequence.push(symbol);
+                    current = previous;
+                }
+                return Some(sequence.into_iter().rev().collect());
+            }
+            for &symbol in &symbols {
+                let next = (step(pair.0, symbol), step(pair.1, symbol));
+                if next != (None, None) && !parents.contains_key(&next) {
+                    parents.insert(next, Some((pair, symbol)));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Check whether every prefix of an accepted string is also accepted
+    /// That's the case exactly when every state on some path from the start
+    /// to an accept state is itself an accept state.
+    pub fn is_prefix_closed(&self) -> bool {
+        let coaccessible = self.get_coaccessible();
+        self.get_reachable().iter()
+                .filter(|state| coaccessible.contains(state))
+                .all(|state| self.accept_states.contains(state))
+    }
+
+    /// Send every undefined transition on a symbol in `alphabet` to a single
+    /// new, non-accepting trap state that loops to itself
+    /// Does nothing (and adds no trap) if no transition is missing, so
+    /// completing twice is the same as completing once.
+    pub fn complete(&mut self, alphabet: &HashSet<char>) {
+        self.alphabet.extend(alphabet);
+        let missing = |trans: &DFATransitionMap| alphabet.iter().any(|symbol| !trans.contains_key(symbol));
+        if !self.table.iter().any(missing) {
+            return;
+        }
+
+        let trap = self.add_state();
+        for trans in &mut self.table {
+            for &symbol in alphabet {
+                trans.entry(symbol).or_insert(trap);
+            }
+        }
+    }
+
+    /// Get a DFA accepting exactly the strings over `alphabet` that this one rejects
+    /// Undefined transitions are sent to a new trap state first (which then
+    /// becomes accepting), and transitions on symbols outside the alphabet
+    /// are dropped.
+    pub fn complement(&self, alphabet: &HashSet<char>) -> DFA {
+        let trap = self.table.len() as StateId;
+        let mut table = self.table.iter()
+                .map(|trans| alphabet.iter()
+                    .map(|&symbol| (symbol, *trans.get(&symbol).unwrap_or(&trap)))
+                    .collect())
+                .collect::<Vec<DFATransitionMap>>();
+        table.push(alphabet.iter().map(|&symbol| (symbol, trap)).collect());
+
+        let accept_states = (0..table.len() as StateId)
+                .filter(|state| !self.accept_states.contains(state))
+                .collect();
+        DFA {table, accept_states, alphabet: alphabet.clone()}
+    }
+
+    /// Get the minimal DFA for the complement of this one's language
+    /// The trap state added by `complement()` is accepting, so it survives
+    /// minimization whenever it can actually be reached.
+    pub fn complement_minimal(&self, alphabet: &HashSet<char>) -> Box<DFA> {
+        Box::new(self.complement(alphabet).minimize())
+    }
+
+    /// Get the minimal DFA recognizing the same language
+    /// Unreachable states are dropped, equivalent states are merged with
+    /// Moore's partition refinement, and states that can never reach an
+    /// accept state are removed (leaving undefined transitions in their
+    /// place). States are renumbered in breadth-first order from the start.
+    pub fn minimize(&self) -> DFA {
+        let mut states = self.get_reachable().into_iter().collect::<Vec<StateId>>();
+        states.sort_unstable();
+        let index = states.iter()
+                .enumerate()
+                .map(|(i, &state)| (state, i))
+                .collect::<HashMap<StateId, usize>>();
+        let mut symbols = states.iter()
+                .flat_map(|&state| self.get(state).keys().copied())
+                .collect::<Vec<char>>();
+        symbols.sort_unstable();
+        symbols.dedup();
+
+        // An extra "dead" state stands in for every undefined transition
+        let dead = states.len();
+        let next = |i: usize, symbol: char| match states.get(i) {
+            Some(&state) => self.get(state).get(&symbol).map_or(dead, |next| index[next]),
+            None => dead
+        };
+        let accepting = |i: usize| i != dead && self.accept_states.contains(&states[i]);
+
+        // Start with accepting vs. non-accepting, then keep splitting blocks
+        // whose members disagree on which block some symbol leads to
+        let mut block = (0..=dead).map(|i| accepting(i) as usize).collect::<Vec<usize>>();
+        let mut num_blocks = block.iter().collect::<HashSet<&usize>>().len();
+        loop {
+            let mut signatures: HashMap<(usize, Vec<usize>), usize> = HashMap::new();
+            let refined = (0..=dead).map(|i| {
+                let signature = (block[i], symbols.iter().map(|&c| block[next(i, c)]).collect());
+                let new_block = signatures.len();
+                *signatures.entry(signature).or_insert(new_block)
+            }).collect::<Vec<usize>>();
+            block = refined;
+            if signatures.len() == num_blocks { break }
+            num_blocks = signatures.len();
+        }
+
+        // Anything in the same block as the dead state can be left out
+        let dead_block = block[dead];
+        let start_block = block[index[&0]];
+        if start_block == dead_block {
+            return DFA {table: vec![HashMap::new()], accept_states: HashSet::new(), alphabet: self.alphabet.clone()};
+        }
+
+        // Number the remaining blocks in breadth-first order
+        let mut representative = HashMap::new();
+        for (i, &b) in block.iter().enumerate().rev() {
+            representative.insert(b, i);
+        }
+        let mut order = vec![start_block];
+        let mut new_ids = HashMap::new();
+        new_ids.insert(start_block, 0 as StateId);
+        let mut table = vec![];
+        while table.len() < order.len() {
+            let rep = representative[&order[table.len()]];
+            let mut transitions = HashMap::new();
+            for &symbol in &symbols {
+                let target = block[next(rep, symbol)];
+                if target == dead_block { continue }
+                let id = *new_ids.entry(target).or_insert_with(|| {
+                    order.push(target);
+                    (order.len() - 1) as StateId
+                });
+                transitions.insert(symbol, id);
+            }
+            table.push(transitions);
+        }
+
+        let accept_states = order.iter()
+                .enumerate()
+                .filter(|(_, b)| accepting(representative[b]))
+                .map(|(id, _)| id as StateId)
+                .collect();
+        DFA {table, accept_states, alphabet: self.alphabet.clone()}
+    }
+
+    /// Get a copy of this DFA without its dead states (those that can never
+    /// reach an accept state), leaving undefined transitions in their place
+    /// The start state is always kept, and the rest keep their relative order.
+    pub fn prune_dead(&self) -> DFA {
+        let coaccessible = self.get_coaccessible();
+        let kept = (0..self.table.len() as StateId)
+                .filter(|state| *state == 0 || coaccessible.contains(state))
+                .collect::<Vec<StateId>>();
+        let new_ids = kept.iter()
+                .enumerate()
+                .map(|(id, &state)| (state, id as StateId))
+                .collect::<HashMap<StateId, StateId>>();
+
+        let table = kept.iter()
+                .map(|&state| self.get(state).iter()
+                    .filter_map(|(&symbol, next)| new_ids.get(next).map(|&id| (symbol, id)))
+                    .collect())
+                .collect();
+        let accept_states = self.accept_states.iter()
+                .filter_map(|state| new_ids.get(state).copied())
+                .collect();
+        DFA {table, accept_states, alphabet: self.alphabet()}
+    }
+
+    /// Get a copy of this DFA without its unreachable states
+    /// The rest keep their relative order, so the start state is still 0.
+    fn prune_unreachable(&self) -> DFA {
+        let reachable = self.get_reachable();
+        let new_ids = (0..self.table.len() as StateId)
+                .filter(|state| reachable.contains(state))
+                .enumerate()
+                .map(|(id, state)| (state, id as StateId))
+                .collect::<HashMap<StateId, StateId>>();
+
+        let mut table = vec![HashMap::new(); new_ids.len()];
+        for (&state, &id) in &new_ids {
+            table[id as usize] = self.get(state).iter()
+                    .map(|(&symbol, next)| (symbol, new_ids[next]))
+                    .collect();
+        }
+        let accept_states = self.accept_states.iter()
+                .filter_map(|state| new_ids.get(state).copied())
+                .collect();
+        DFA {table, accept_states, alphabet: self.alphabet.clone()}
+    }
+
+    /// For each length from 0 to `max_len`, count the distinct states that
+    /// some string of exactly that length (over `alphabet`) can end in
+    pub fn state_frontier_sizes(&self, max_len: usize, alphabet: &HashSet<char>) -> Vec<usize> {
+        let mut frontier: HashSet<StateId> = [0].iter().copied().collect();
+        let mut sizes = vec![frontier.len()];
+        for _ in 0..max_len {
+            frontier = frontier.iter()
+                    .flat_map(|&state| self.get(state).iter()
+                        .filter(|(label, _)| alphabet.contains(label))
+                        .map(|(_, &next)| next))
+                    .collect();
+            sizes.push(frontier.len());
+        }
+        sizes
+    }
+
+    /// Get a DFA recognizing every factor (substring) of every string in
+    /// this DFA's language, including the empty string if the language
+    /// isn't empty
+    /// Every useful state (reachable and able to reach an accept state)
+    /// becomes both a start and an accept state, and the result is determinized.
+    pub fn factors(&self) -> Box<DFA> {
+        let useful = self.get_reachable()
+                .intersection(&self.get_coaccessible())
+                .copied()
+                .collect::<HashSet<StateId>>();
+        nfa_to_dfa(&self.to_multi_start_nfa(&useful, &useful, &useful))
+    }
+
+    /// List every string this DFA accepts with at most `max_len` characters,
+    /// shortest first (and alphabetically within each length)
+    pub fn enumerate(&self, max_len: usize) -> Vec<String> {
+        let coaccessible = self.get_coaccessible();
+        let mut accepted = vec![];
+        let mut frontier = vec![(String::new(), 0)];
+        for length in 0..=max_len {
+            frontier.retain(|(_, state)| coaccessible.contains(state));
+            frontier.sort();
+            accepted.extend(frontier.iter()
+                    .filter(|(_, state)| self.accept_states.contains(state))
+                    .map(|(string, _)| string.clone()));
+            if length == max_len {
+                break;
+            }
+            frontier = frontier.iter()
+                    .flat_map(|(string, state)| self.get(*state).iter()
+                        .map(move |(&symbol, &next)| (format!("{}{}", string, symbol), next)))
+                    .collect();
+        }
+        accepted
+    }
+
+    /// Get an NFA accepting every string that can be made from an accepted
+    /// string by repeatedly swapping neighboring symbols that are independent
+    /// of each other (a pair in `independent`, in either order)
+    /// The closure of a regular language needn't be regular, so this only
+    /// considers accepted strings of at most `max_len` characters.
+    pub fn commutation_closure(&self, independent: &[(char, char)], max_len: usize) -> Box<NFA> {
+        let commutes = |a: char, b: char| independent.contains(&(a, b)) || independent.contains(&(b, a));
+        let mut closure: HashSet<String> = HashSet::new();
+        for word in self.enumerate(max_len) {
+            let mut stack = vec![word.chars().collect::<Vec<char>>()];
+            while let Some(word) = stack.pop() {
+                if !closure.insert(word.iter().collect()) {
+                    continue;
+                }
+                for i in 1..word.len() {
+                    if word[i - 1] != word[i] && commutes(word[i - 1], word[i]) {
+                        let mut swapped = word.clone();
+                        swapped.swap(i - 1, i);
+                        stack.push(swapped);
+                    }
+                }
+            }
+        }
+
+        let words = closure.iter().map(String::as_str).collect::<Vec<&str>>();
+        let trie = trie(&words);
+        let states = (0..trie.table.len() as StateId).collect();
+        let start = [0].iter().copied().collect();
+        Box::new(trie.to_multi_start_nfa(&states, &start, &trie.accept_states))
+    }
+
+    /// Lazily list the strings this DFA accepts (over `alphabet`) in strict
+    /// lexicographic order, e.g. `""`, `"a"`, `"aa"`, ... for `a*b*`
+    /// This is a depth-first search that never enters states that can't reach
+    /// an accept state, so memory grows only with the length of the current
+    /// string. Beware that some languages have no lexicographically first
+    /// string (`a*b` has ... < `aab` < `ab`), in which case the iterator
+    /// searches forever without yielding.
+    pub fn lexicographic_iter<'a>(&'a self, alphabet: &'a [char]) -> impl Iterator<Item = String> + 'a {
+        let mut symbols = alphabet.to_vec();
+        symbols.sort_unstable();
+        symbols.dedup();
+
+        let coaccessible = self.get_coaccessible();
+        let mut stack = vec![];
+        if coaccessible.contains(&0) {
+            stack.push((String::new(), 0));
+        }
+
+        std::iter::from_fn(move || {
+            while let Some((prefix, state)) = stack.pop() {
+                // push in reverse so the smallest symbol is explored first
+                for symbol in symbols.iter().rev() {
+                    match self.get(state).get(symbol) {
+                        Some(next) if coaccessible.contains(next) => {
+                            let mut string = prefix.clone();
+                            string.push(*symbol);
+                            stack.push((string, *next));
+                        },
+                        _ => ()
+                    }
+                }
+                if self.accept_states.contains(&state) {
+                    return Some(prefix);
+                }
+            }
+            None
+        })
+    }
+
+    /// Get an NFA recognizing every cyclic shift of every string this DFA
+    /// accepts, i.e. `vu` for each accepted `uv`
+    /// For each possible split state `p`, the NFA guesses `p`, reads `v` in a
+    /// copy of the DFA running from `p` to an accept state, then reads `u` in
+    /// a second copy running from the start back to `p`. That makes two
+    /// copies per state, so the NFA has about 2n^2 states.
+    pub fn cyclic_shifts(&self) -> Box<NFA> {
+        let useful = self.get_reachable()
+                .intersection(&self.get_coaccessible())
+                .copied()
+                .collect::<HashSet<StateId>>();
+        let n = self.table.len();
+        let first_half = |split: StateId, state: StateId| state_id(1 + 2 * split as usize * n + state as usize);
+        let second_half = |split: StateId, state: StateId| state_id(1 + (2 * split as usize + 1) * n + state as usize);
+
+        let mut nfa = NFA::new();
+        let start = nfa.add_state();
+        for _ in 0..2 * n * n {
+            nfa.add_state();
+        }
+        nfa.accept_state = nfa.add_state();
+
+        for &split in &useful {
+            for &state in &useful {
+                for (&label, &next) in self.get(state) {
+                    if useful.contains(&next) {
+                        nfa.add_transition(first_half(split, state), first_half(split, next), label);
+                        nfa.add_transition(second_half(split, state), second_half(split, next), label);
+                    }
+                }
+            }
+        }
+
+        // epsilons go in last, in the order they're followed, so they fold in everything
+        for &split in &useful {
+            nfa.add_epsilon(start, first_half(split, split));
+        }
+        for &split in &useful {
+            for &accept in self.accept_states.intersection(&useful) {
+                nfa.add_epsilon(first_half(split, accept), second_half(split, 0));
+            }
+        }
+        for &split in &useful {
+            nfa.add_epsilon(second_half(split, split), nfa.accept_state);
+        }
+        Box::new(nfa)
+    }
+
+    /// Get an NFA accepting every string over `alphabet` within Hamming
+    /// distance `radius` of some string this DFA accepts (i.e. with at most
+    /// `radius` symbols substituted)
+    /// There's a copy of the DFA for each number of substitutions used so far,
+    /// and a substitution moves along a transition into the next copy.
+    pub fn hamming_ball(&self, radius: usize, alphabet: &HashSet<char>) -> Box<NFA> {
+        let n = self.table.len();
+        let id = |state: StateId, edits: usize| state_id(edits * n + state as usize);
+
+        let mut nfa = NFA::new();
+        for _ in 0..n * (radius + 1) {
+            nfa.add_state();
+        }
+        nfa.accept_state = nfa.add_state();
+
+        for edits in 0..=radius {
+            for (state, transitions) in self.table.iter().enumerate() {
+                let state = state as StateId;
+                for (&label, &next) in transitions {
+                    nfa.add_transition(id(state, edits), id(next, edits), label);
+                    if edits < radius {
+                        for &symbol in alphabet.iter().filter(|&&symbol| symbol != label) {
+                            nfa.add_transition(id(state, edits), id(next, edits + 1), symbol);
+                        }
+                    }
+                }
+            }
+        }
+        for edits in 0..=radius {
+            for &state in &self.accept_states {
+                nfa.add_epsilon(id(state, edits), nfa.accept_state);
+            }
+        }
+        Box::new(nfa)
+    }
+
+    /// Find the longest string over `alphabet` that this DFA rejects, or None
+    /// if it rejects infinitely many strings (or none at all)
+    /// Ties are broken alphabetically. This is a longest-path search over the
+    /// complement, which has no cycles among its useful states exactly when
+    /// it's finite. The useful states are sorted topologically first, so the
+    /// search is a single pass with no recursion.
+    pub fn longest_rejected(&self, alphabet: &HashSet<char>) -> Option<String> {
+        let complement = self.complement(alphabet);
+        let useful = complement.get_reachable()
+                .intersection(&complement.get_coaccessible())
+                .copied()
+                .collect::<HashSet<StateId>>();
+        if !useful.contains(&0) {
+            return None;
+        }
+        let mut symbols = alphabet.iter().copied().collect::<Vec<char>>();
+        symbols.sort_unstable();
+
+        // put the useful states in topological order (Kahn's algorithm); any
+        // left over are on a cycle, so infinitely many strings are rejected
+        let mut in_degree: HashMap<StateId, usize> = useful.iter().map(|&state| (state, 0)).collect();
+        for &state in &useful {
+            for symbol in &symbols {
+                if let Some(degree) = in_degree.get_mut(&complement.get(state)[symbol]) {
+                    *degree += 1;
+                }
+            }
+        }
+        let mut worklist = in_degree.iter()
+                .filter(|(_, &degree)| degree == 0)
+                .map(|(&state, _)| state)
+                .collect::<Vec<StateId>>();
+        let mut order = vec![];
+        while let Some(state) = worklist.pop() {
+            order.push(state);
+            for symbol in &symbols {
+                let next = complement.get(state)[symbol];
+                if let Some(degree) = in_degree.get_mut(&next) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        worklist.push(next);
+                    }
+                }
+            }
+        }
+        if order.len() < useful.len() {
+            return None;
+        }
+
+        // the length of the longest path from each state to an accept state,
+        // and its first step, found backwards so every successor comes first
+        let mut longest: HashMap<StateId, (usize, Option<(char, StateId)>)> = HashMap::new();
+        for &state in order.iter().rev() {
+            let mut best = if complement.accept_states.contains(&state) { Some((0, None)) } else { None };
+            for &symbol in &symbols {
+                let next = complement.get(state)[&symbol];
+                if let Some(&(length, _)) = longest.get(&next) {
+                    // None sorts first, so any path beats having none yet
+                    if best.map(|(best, _)| best) < Some(length + 1) {
+                        best = Some((length + 1, Some((symbol, next))));
+                    }
+                }
+            }
+            longest.insert(state, best.unwrap()); // useful states always reach an accept state
+        }
+
+        let mut string = String::new();
+        let mut state = 0;
+        while let (_, Some((symbol, next))) = longest[&state] {
+            string.push(symbol);
+            state = next;
+        }
+        Some(string)
+    }
+
+    /// Count the distinct transformations of the state set that input strings
+    /// induce (the size of the transition monoid), including the identity
+    /// from the empty string
+    /// Each transformation maps every state to where the string leads from
+    /// it, or None if some transition along the way is undefined. There are
+    /// finitely many of these, so the search always ends.
+    pub fn transition_monoid_size(&self) -> usize {
+        self.transition_monoid().len()
+    }
+
+    /// Check whether this DFA's language is star-free, i.e. whether the
+    /// transition monoid of its minimal DFA is aperiodic: every element `m`
+    /// has some `n` where `m^(n+1) = m^n`, so repeating a string eventually
+    /// stops making a difference
+    /// `a*` is aperiodic, but `(aa)*` isn't since it has to count mod 2.
+    pub fn is_aperiodic(&self) -> bool {
+        self.minimize().transition_monoid().iter().all(|element| {
+            // take powers until one repeats; the cycle they fall into must be a fixed point
+            let mut powers = vec![element.clone()];
+            loop {
+                let next = compose(powers.last().unwrap(), element);
+                if let Some(i) = powers.iter().position(|power| *power == next) {
+                    return i == powers.len() - 1;
+                }
+                powers.push(next);
+            }
+        })
+    }
+
+    /// Get every transformation of the state set that some input string
+    /// induces, as a list of where each state ends up
+    fn transition_monoid(&self) -> HashSet<Transformation> {
+        let mut symbols = self.table.iter()
+                .flat_map(|trans| trans.keys().copied())
+                .collect::<Vec<char>>();
+        symbols.sort_unstable();
+        symbols.dedup();
+
+        let identity = (0..self.table.len() as StateId).map(Some).collect::<Transformation>();
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert(identity.clone());
+        queue.push_back(identity);
+
+        // extend each transformation by one more symbol until nothing new turns up
+        while let Some(transform) = queue.pop_front() {
+            for symbol in &symbols {
+                let next = transform.iter()
+                        .map(|state| state.and_then(|state| self.get(state).get(symbol).copied()))
+                        .collect::<Transformation>();
+                if !seen.contains(&next) {
+                    seen.insert(next.clone());
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen
+    }
+
+    /// List the transitions that can never be part of an accepting run, because
+    /// their source is unreachable or their target can't reach an accept state
+    /// The list is sorted by source state, then symbol.
+    pub fn useless_transitions(&self) -> Vec<(StateId, char, StateId)> {
+        let reachable = self.get_reachable();
+        let coaccessible = self.get_coaccessible();
+        let mut useless = self.all_edges().into_iter()
+                .filter(|(from, to, _label)| !reachable.contains(from) || !coaccessible.contains(to))
+                .map(|(from, to, label)| (from, label, to))
+                .collect::<Vec<(StateId, char, StateId)>>();
+        useless.sort_unstable();
+        useless
+    }
+
+    /// Convert this DFA back into a regex using state elimination
+    /// The DFA becomes a generalized NFA, whose edges are labeled with regexes,
+    /// with a fresh start state and a fresh accept state. Then every original
+    /// state is ripped out in turn, replacing each path through it with a
+    /// single edge. The label left between the fresh states describes the
+    /// whole language.
+    /// The grammar has no way to write the empty string or the empty language
+    /// directly, so those come out as `a{0}` and `[^ 0-9a-z]` (which matches
+    /// nothing, since it excludes the whole character set).
+    pub fn to_regex(&self) -> String {
+        use std::collections::BTreeMap;
+
+        // only states on some accepting path contribute anything
+        let coaccessible = self.get_coaccessible();
+        let mut states = self.get_reachable().intersection(&coaccessible).copied().collect::<Vec<StateId>>();
+        states.sort_unstable();
+        let start = self.table.len();
+        let accept = start + 1;
+
+        // group the symbols on each edge so parallel transitions share a label
+        let mut symbols: BTreeMap<(usize, usize), HashSet<char>> = BTreeMap::new();
+        for &(from, to, symbol) in &self.edges() {
+            if states.contains(&from) && states.contains(&to) {
+                symbols.entry((from as usize, to as usize)).or_default().insert(symbol);
+            }
+        }
+        let mut edges = symbols.iter()
+                .map(|(&edge, symbols)| (edge, EdgeRegex::symbols(symbols)))
+                .collect::<BTreeMap<(usize, usize), EdgeRegex>>();
+        if states.contains(&0) {
+            edges.insert((start, 0), EdgeRegex::Epsilon);
+        }
+        for &state in states.iter().filter(|state| self.accept_states.contains(state)) {
+            edges.insert((state as usize, accept), EdgeRegex::Epsilon);
+        }
+
+        // rip out each state, rerouting the paths through it
+        for rip in states.into_iter().map(|state| state as usize) {
+            let self_loop = edges.remove(&(rip, rip)).map(|regex| regex.star()).unwrap_or(EdgeRegex::Epsilon);
+            let incoming = edges.iter()
+                    .filter(|((_, to), _)| *to == rip)
+                    .map(|(&(from, _), regex)| (from, regex.clone()))
+                    .collect::<Vec<(usize, EdgeRegex)>>();
+            let outgoing = edges.iter()
+                    .filter(|((from, _), _)| *from == rip)
+                    .map(|(&(_, to), regex)| (to, regex.clone()))
+                    .collect::<Vec<(usize, EdgeRegex)>>();
+            edges.retain(|&(from, to), _| from != rip && to != rip);
+            for (from, into) in &incoming {
+                for (to, out) in &outgoing {
+                    let path = into.concat(&self_loop).concat(out);
+                    let combined = match edges.get(&(*from, *to)) {
+                        Some(existing) => existing.union(&path),
+                        None => path
+                    };
+                    edges.insert((*from, *to), combined);
+                }
+            }
+        }
+
+        match edges.get(&(start, accept)) {
+            None => "[^ 0-9a-z]".to_string(),
+            Some(EdgeRegex::Epsilon) => "a{0}".to_string(),
+            Some(EdgeRegex::Regex(regex, _)) => regex.clone()
+        }
+    }
+
+    /// Get the GraphViz representation of this DFA
+    pub fn to_graph(&self) -> String {
+        crate::graphviz::generate(0, &self.accept_states, &self.edges())
+    }
+
+    /// Get a plain-text transition table with a row for each reachable state
+    /// and a column for each symbol, e.g. for `ab`:
+    /// ```text
+    ///       | a  | b
+    /// →  q0 | q1 |
+    ///    q1 |    | q2
+    ///  * q2 |    |
+    /// ```
+    /// The start state is marked with `→`, accept states with `*`, and
+    /// undefined transitions are left blank.
+    pub fn to_table(&self) -> String {
+        let mut states = self.get_reachable().into_iter().collect::<Vec<StateId>>();
+        states.sort_unstable();
+        let mut alphabet = self.alphabet().into_iter().collect::<Vec<char>>();
+        alphabet.sort_unstable();
+
+        // build every cell first so the columns can be sized to fit
+        let header = std::iter::once(String::new())
+                .chain(alphabet.iter().map(char::to_string))
+                .collect::<Vec<String>>();
+        let rows = states.iter().map(|&state| {
+            let arrow = if state == 0 { '\u{2192}' } else { ' ' };
+            let star = if self.accept_states.contains(&state) { '*' } else { ' ' };
+            std::iter::once(format!("{}{} q{}", arrow, star, state))
+                    .chain(alphabet.iter().map(|symbol| match self.get(state).get(symbol) {
+                        Some(next) => format!("q{}", next),
+                        None => String::new()
+                    }))
+                    .collect::<Vec<String>>()
+        }).collect::<Vec<Vec<String>>>();
+        let widths = (0..header.len())
+                .map(|col| std::iter::once(&header).chain(&rows)
+                    .map(|row| row[col].chars().count())
+                    .max().unwrap())
+                .collect::<Vec<usize>>();
+
+        std::iter::once(&header).chain(&rows)
+                .map(|row| row.iter()
+                    .zip(&widths)
+                    .map(|(cell, &width)| format!("{:<width$}", cell, width=width))
+                    .collect::<Vec<String>>()
+                    .join(" | ")
+                    .trim_end()
+                    .to_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+    }
+
+    /// Get the reachable part of this DFA as a JSON object, e.g.
+    /// `{"start":0,"accept":[1],"transitions":[{"from":0,"symbol":"a","to":1}]}`
+    /// Accept states and transitions are sorted, so equal DFAs give equal JSON.
+    pub fn to_json(&self) -> String {
+        let reachable = self.get_reachable();
+        let mut accept = self.accept_states.iter()
+                .copied()
+                .filter(|state| reachable.contains(state))
+                .collect::<Vec<StateId>>();
+        accept.sort_unstable();
+        let mut edges = self.edges();
+        edges.sort_unstable_by_key(|&(from, _, symbol)| (from, symbol));
+
+        let record = GraphRecord {
+            start: 0,
+            accept,
+            transitions: edges.into_iter()
+                    .map(|(from, to, symbol)| TransitionRecord {from, symbol, to})
+                    .collect()
+        };
+        serde_json::to_string(&record).unwrap()
+    }
+
+    /// Get the Mermaid representation of this DFA, e.g. to paste into Markdown
+    pub fn to_mermaid(&self) -> String {
+        crate::mermaid::generate(0, &self.accept_states, &self.edges())
+    }
+
+    /// Get the GraphViz representation of every state in this DFA, including
+    /// unreachable ones (drawn dashed), e.g. for debugging `nfa_to_dfa()`
+    pub fn to_graph_full(&self) -> String {
+        let reachable = self.get_reachable();
+        let mut unreachable = (0..self.table.len() as StateId)
+                .filter(|state| !reachable.contains(state))
+                .collect::<Vec<StateId>>();
+        unreachable.sort_unstable();
+        crate::graphviz::generate_dashed(0, &self.accept_states, &unreachable, &self.all_edges())
+    }
+
+    /// Get a GraphViz graph showing this DFA next to another one, e.g. to
+    /// compare a machine before and after minimization
+    pub fn to_comparison_graph(&self, other: &DFA, labels: (&str, &str)) -> String {
+        use crate::graphviz::{cluster, generate_clusters};
+        generate_clusters(&[
+            cluster(0, labels.0, 0, &self.accept_states, &self.edges()),
+            cluster(1, labels.1, 0, &other.accept_states, &other.edges())
+        ])
+    }
+
+    /// Group the symbols used by this DFA into classes that behave
+    /// identically from every state (e.g. all ten digits in `\d+`)
+    /// Classes are ordered by their smallest symbol.
+    pub fn symbol_classes(&self) -> Vec<HashSet<char>> {
+        let mut symbols = self.table.iter()
+                .flat_map(|trans| trans.keys().copied())
+                .collect::<Vec<char>>();
+        symbols.sort_unstable();
+        symbols.dedup();
+
+        // two symbols are in the same class if their columns of the table match
+        let mut classes: Vec<(Vec<Option<StateId>>, HashSet<char>)> = vec![];
+        for symbol in symbols {
+            let column = self.table.iter()
+                    .map(|trans| trans.get(&symbol).copied())
+                    .collect::<Vec<Option<StateId>>>();
+            match classes.iter_mut().find(|(other, _)| *other == column) {
+                Some((_, class)) => { class.insert(symbol); },
+                None => classes.push((column, [symbol].iter().copied().collect()))
+            }
+        }
+        classes.into_iter().map(|(_, class)| class).collect()
+    }
+
+    /// Get the GraphViz representation of this DFA with one edge per
+    /// symbol class instead of one edge per symbol
+    pub fn to_classed_graph(&self) -> String {
+        let classes = self.symbol_classes();
+        let labels = classes.iter().map(class_label).collect::<Vec<String>>();
+
+        // every member of a class has the same edge, so keep the first one we see
+        let mut edges = vec![];
+        let mut seen = HashSet::new();
+        for (from, to, symbol) in self.edges() {
+            let class = classes.iter().position(|class| class.contains(&symbol)).unwrap();
+            if seen.insert((from, class)) {
+                edges.push((from, to, labels[class].as_str()));
+            }
+        }
+        crate::graphviz::generate(0, &self.accept_states, &edges)
+    }
+
+    fn edges(&self) -> Vec<crate::graphviz::Edge> {
+        let reachable = self.get_reachable();
+        self.all_edges().into_iter()
+                .filter(|(from, to, _label)| reachable.contains(from) && reachable.contains(to))
+                .collect()
+    }
+
+    fn all_edges(&self) -> Vec<crate::graphviz::Edge> {
+        self.table.iter()
+                .enumerate()
+                .flat_map(|(s, trans)| trans.iter()
+                    .map(move |(label, dest)| (s as StateId, *dest, *label)))
+                .collect()
+    }
+
+    /// Visit every state reachable from `start` exactly once in breadth-first
+    /// order, passing each state and its transition map to `visit`
+    pub fn bfs_from(&self, start: StateId, mut visit: impl FnMut(StateId, &DFATransitionMap)) {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert(start);
+        queue.push_back(start);
+
+        while let Some(state) = queue.pop_front() {
+            let transitions = self.get(state);
+            visit(state, transitions);
+            for &next in transitions.values() {
+                if visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    fn get_reachable(&self) -> HashSet<StateId> {
+        let mut reachable = HashSet::new();
+        self.bfs_from(0, |state, _| { reachable.insert(state); });
+        reachable
+    }
+
+    /// Copy the given states of this DFA into an NFA with a fresh start state
+    /// that has epsilon transitions into each of `starts`, and a fresh accept
+    /// state with epsilon transitions out of each of `accepts`
+    /// DFA state `s` becomes NFA state `s + 1`, since the NFA starts at 0.
+    fn to_multi_start_nfa(&self, states: &HashSet<StateId>, starts: &HashSet<StateId>,
+            accepts: &HashSet<StateId>) -> NFA {
+        let mut nfa = NFA::new();
+        let start = nfa.add_state();
+        for _ in 0..self.table.len() {
+            nfa.add_state();
+        }
+        nfa.accept_state = nfa.add_state();
+
+        for &state in states {
+            for (&label, &next) in self.get(state) {
+                if states.contains(&next) {
+                    nfa.add_transition(state + 1, next + 1, label);
+                }
+            }
+        }
+
+        // epsilons go in last, source side first, so they fold in every transition
+        for &state in starts {
+            nfa.add_epsilon(start, state + 1);
+        }
+        for &state in accepts {
+            nfa.add_epsilon(state + 1, nfa.accept_state);
+        }
+        nfa
+    }
+
+    /// Get the states from which some accept state is reachable
+    fn get_coaccessible(&self) -> HashSet<StateId> {
+        // invert the transition table
+        let mut predecessors: MultiMap<StateId, StateId> = HashMap::new();
+        for (state, transitions) in self.table.iter().enumerate() {
+            for &next in transitions.values() {
+                predecessors.add_multi(next, state as StateId);
+            }
+        }
+
+        // walk backwards from the accept states
+        let mut coaccessible = self.accept_states.clone();
+        let mut stack = coaccessible.iter().copied().collect::<Vec<StateId>>();
+        while let Some(state) = stack.pop() {
+            for &prev in predecessors.get_multi_ref(state).into_iter().flatten() {
+                if coaccessible.insert(prev) {
+                    stack.push(prev);
+                }
+            }
+        }
+        coaccessible
+    }
+}
+
+#[test]
+fn test_intersects() {
+    use crate::regex_to_dfa;
+    let alphabet = "ab".chars().collect();
+    let intersects = |a, b| regex_to_dfa(a).unwrap().intersects(&regex_to_dfa(b).unwrap(), &alphabet);
+
+    assert!(intersects("ab*", "a*b"));
+    assert!(intersects("(ab)+", "a(ba)*b"));
+    assert!(intersects("a*", "b*")); // both accept the empty string
+    assert!(!intersects("a", "b"));
+    assert!(!intersects("aa*", "bb*"));
+    assert!(!intersects("ab", "abb"));
+
+    // agrees with building the whole product
+    let regexes = ["ab*", "a*b", "(ab)+", "a(ba)*b", "a*", "b*", "a", "b", "aa*", "bb*", "ab", "abb"];
+    for a in &regexes {
+        for b in &regexes {
+            let (dfa_a, dfa_b) = (regex_to_dfa(a).unwrap(), regex_to_dfa(b).unwrap());
+            assert_eq!(dfa_a.intersects(&dfa_b, &alphabet), !dfa_a.intersect(&dfa_b).is_empty(), "{} and {}", a, b);
+        }
+    }
+
+    // symbols outside the alphabet are ignored
+    let only_a = "a".chars().collect();
+    assert!(!regex_to_dfa("b").unwrap().intersects(&regex_to_dfa("a|b").unwrap(), &only_a));
+}
+
+#[test]
+fn test_bfs_from() {
+    let dfa = crate::regex_to_dfa("abc").unwrap();
+
+    let mut visited = vec![];
+    dfa.bfs_from(0, |state, _| visited.push(state));
+    assert_eq!(visited.len(), 4);
+    assert_eq!(visited[0], 0);
+
+    // starting partway along the chain only sees what's downstream
+    let mut count = 0;
+    dfa.bfs_from(dfa.get(visited[1])[&'b'], |_, transitions| {
+        count += 1;
+        assert!(transitions.len() <= 1);
+    });
+    assert_eq!(count, 2);
+}
+
+#[test]
+fn test_is_prefix_closed() {
+    use crate::regex_to_dfa;
+    assert!(regex_to_dfa("a*").unwrap().is_prefix_closed());
+    assert!(regex_to_dfa("(a|b)*").unwrap().is_prefix_closed());
+
+    assert!(!regex_to_dfa("ab").unwrap().is_prefix_closed());
+    assert!(!regex_to_dfa("a*b").unwrap().is_prefix_closed());
+    assert!(!regex_to_dfa("(ab)*").unwrap().is_prefix_closed());
+}
+
+#[test]
+fn test_symbol_classes() {
+    use crate::regex_to_dfa;
+    let digits = ('0'..='9').collect::<HashSet<char>>();
+    assert_eq!(regex_to_dfa("\\d+").unwrap().symbol_classes(), vec![digits.clone()]);
+
+    // `a` and `b` lead to different places, but the digits still stick together
+    let classes = regex_to_dfa("a\\d|b").unwrap().symbol_classes();
+    assert_eq!(classes.len(), 3);
+    assert!(classes.contains(&digits));
+
+    let graph = regex_to_dfa("\\d+").unwrap().to_classed_graph();
+    assert!(graph.contains("[label=\"[0-9]\"]"));
+    assert!(!graph.contains("[label=\"5\"]"));
+
+    let class = ['a', 'c', 'd', 'e', 'x', 'y'].iter().copied().collect();
+    assert_eq!(class_label(&class), "[ac-exy]");
+}
+
+#[test]
+fn test_comparison_graph() {
+    use crate::regex_to_dfa;
+    let graph = regex_to_dfa("ab").unwrap().to_comparison_graph(&regex_to_dfa("a|b").unwrap(), ("before", "after"));
+
+    assert!(graph.starts_with("digraph {\n    rankdir=LR;\n    subgraph cluster_0 {\n        label=\"before\";\n"));
+    assert!(graph.contains("    subgraph cluster_1 {\n        label=\"after\";\n"));
+    assert!(graph.contains("c0_start -> c0_0;\n"));
+    assert!(graph.contains("c1_start -> c1_0;\n"));
+
+    // the first machine has an `a` then `b` chain, the second branches from the start
+    assert!(graph.contains("c0_0 -> c0_1 [label=\"a\"];\n"));
+    assert!(graph.contains("c1_0 -> c1_1 [label=\"a,b\"];\n"));
+}
+
+#[test]
+fn test_merged_edges() {
+    use crate::regex_to_dfa;
+    let graph = regex_to_dfa("\\d").unwrap().to_graph();
+    assert_eq!(graph.matches(" -> ").count(), 2);
+    assert!(graph.contains("    0 -> 1 [label=\"0-9\"];\n"));
+
+    let graph = regex_to_dfa("[a-c]d").unwrap().to_graph();
+    assert!(graph.contains("    0 -> 1 [label=\"a-c\"];\n"));
+    assert!(graph.contains("    1 -> 2 [label=\"d\"];\n"));
+}
+
+#[test]
+fn test_accepts_reversed() {
+    use crate::regex_to_dfa;
+    let dfa = regex_to_dfa("ab*c").unwrap();
+    assert!(dfa.accepts_reversed("ca"));
+    assert!(dfa.accepts_reversed("cbbba"));
+    assert!(!dfa.accepts_reversed("abc"));
+    assert!(!dfa.accepts_reversed(""));
+
+    // should always agree with reversing the string up front
+    let dfa = regex_to_dfa("(a|b)*a(a|b)").unwrap();
+    for input in &["", "a", "ab", "ba", "aab", "bab", "abba", "bbaab"] {
+        let reversed = input.chars().rev().collect::<String>();
+        assert_eq!(dfa.accepts_reversed(input), dfa.accepts(&reversed), "{}", input);
+    }
+}
+
+#[test]
+fn test_partition_inputs() {
+    let dfa = crate::regex_to_dfa("ab*").unwrap();
+    let (accepted, rejected) = dfa.partition_inputs(&["a", "", "abb", "ba", "ab", "abab"]);
+    assert_eq!(accepted, vec!["a", "abb", "ab"]);
+    assert_eq!(rejected, vec!["", "ba", "abab"]);
+}
+
+/// Every string over `alphabet` up to length `max_len`
+#[cfg(test)]
+fn all_strings(alphabet: &str, max_len: usize) -> Vec<String> {
+    let mut strings = vec![String::new()];
+    let mut frontier = vec![String::new()];
+    for _ in 0..max_len {
+        frontier = frontier.iter()
+                .flat_map(|prefix| alphabet.chars().map(move |c| format!("{}{}", prefix, c)))
+                .collect();
+        strings.extend(frontier.iter().cloned());
+    }
+    strings
+}
+
+#[test]
+fn test_minimize() {
+    use crate::regex_to_dfa;
+    for (regex, states) in &[("(a|b)*abb", 4), ("a*a*", 1), ("aa*|a", 2), ("ab|ac", 3), ("(ab)*", 2)] {
+        let dfa = regex_to_dfa(regex).unwrap();
+        let minimal = dfa.minimize();
+        assert_eq!(minimal.table.len(), *states, "{}", regex);
+        for input in all_strings("abc", 5) {
+            assert_eq!(minimal.accepts(&input), dfa.accepts(&input), "{} on {:?}", regex, input);
+        }
+    }
+}
+
+#[test]
+fn test_complement() {
+    let alphabet = "ab".chars().collect();
+    let complement = crate::regex_to_dfa("(a|b)*b").unwrap().complement(&alphabet);
+    for accepted in &["", "a", "ba", "bba"] {
+        assert!(complement.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["ab", "b", "aab"] {
+        assert!(!complement.accepts(rejected), "should reject {:?}", rejected);
+    }
+    // symbols outside the alphabet are still rejected
+    assert!(!complement.accepts("c"));
+}
+
+#[test]
+fn test_intersect() {
+    use crate::regex_to_dfa;
+    let dfa = regex_to_dfa("a*b").unwrap().intersect(&regex_to_dfa("ab*").unwrap());
+    assert_eq!(dfa.enumerate(5), vec!["ab"]);
+    assert_eq!(dfa.num_states(), 3);
+
+    // no shared strings leaves nothing to accept
+    let dfa = regex_to_dfa("a+").unwrap().intersect(&regex_to_dfa("b+").unwrap());
+    assert!(dfa.accept_states.is_empty());
+}
+
+#[test]
+fn test_union() {
+    use crate::regex_to_dfa;
+    let dfa = regex_to_dfa("a").unwrap().union(&regex_to_dfa("b").unwrap());
+    assert!(dfa.accepts("a"));
+    assert!(dfa.accepts("b"));
+    assert!(!dfa.accepts("ab"));
+    assert!(!dfa.accepts(""));
+
+    let dfa = regex_to_dfa("ab*").unwrap().union(&regex_to_dfa("a*c").unwrap());
+    assert_eq!(dfa.enumerate(3), vec!["a", "c", "ab", "ac", "aac", "abb"]);
+}
+
+#[test]
+fn test_difference() {
+    use crate::regex_to_dfa;
+    let dfa = regex_to_dfa("a*").unwrap().difference(&regex_to_dfa("aa").unwrap());
+    for accepted in &["", "a", "aaa", "aaaa"] {
+        assert!(dfa.accepts(accepted), "should accept {:?}", accepted);
+    }
+    assert!(!dfa.accepts("aa"));
+
+    // symbols only the other DFA knows about don't get in the way
+    let dfa = regex_to_dfa("a|b").unwrap().difference(&regex_to_dfa("bc").unwrap());
+    assert_eq!(dfa.enumerate(3), vec!["a", "b"]);
+}
+
+#[test]
+fn test_equivalent() {
+    use crate::regex_to_dfa;
+    let equivalent = |a, b| regex_to_dfa(a).unwrap().equivalent(&regex_to_dfa(b).unwrap());
+    assert!(equivalent("a|aa", "aa?"));
+    assert!(equivalent("(a|b)*", "(a*b*)*"));
+    assert!(equivalent("ab*", "ab*|a"));
+    assert!(!equivalent("a|aa", "a+"));
+    assert!(!equivalent("a*", "a+"));
+    assert!(!equivalent("a", "b"));
+}
+
+#[test]
+fn test_to_regex() {
+    use crate::regex_to_dfa;
+
+    // strings over {a, b} ending in a
+    let regex = regex_to_dfa("(a|b)*a").unwrap().to_regex();
+    let dfa = regex_to_dfa(&regex).unwrap();
+    for input in all_strings("ab", 6) {
+        assert_eq!(dfa.accepts(&input), input.ends_with('a'), "{} on {:?}", regex, input);
+    }
+
+    for original in &["ab*", "(a|b)*abb", "a?b?", "(ab|a)*b{1,2}", "[0-3]+x", "\\*|\\(\\)"] {
+        let dfa = regex_to_dfa(original).unwrap();
+        let regex = dfa.to_regex();
+        assert!(dfa.equivalent(&regex_to_dfa(&regex).unwrap()), "{} became {}", original, regex);
+    }
+    assert_eq!(regex_to_dfa("ab").unwrap().to_regex(), "ab");
+
+    // the languages that need special regexes
+    let empty_string = DFA {table: vec![HashMap::new()], accept_states: [0].iter().copied().collect(), alphabet: HashSet::new()};
+    assert_eq!(regex_to_dfa(&empty_string.to_regex()).unwrap().enumerate(3), vec![""]);
+    let empty_language = DFA::empty_language(&"ab".chars().collect());
+    assert!(regex_to_dfa(&empty_language.to_regex()).unwrap().enumerate(3).is_empty());
+}
+
+#[test]
+fn test_trace() {
+    let dfa = crate::regex_to_dfa("ab*a").unwrap();
+    let b_loop = dfa.get(1)[&'b'];
+    let end = dfa.get(1)[&'a'];
+    assert_eq!(dfa.trace("aba"), vec![(0, 'a', Some(1)), (1, 'b', Some(b_loop)), (b_loop, 'a', Some(end))]);
+    assert!(dfa.accept_states.contains(&end));
+
+    // stuck on a missing transition
+    assert_eq!(dfa.trace("bab"), vec![(0, 'b', None)]);
+    assert!(dfa.trace("").is_empty());
+}
+
+#[test]
+fn test_count_accepted() {
+    // strings over {a, b} of even length ending in b
+    let dfa = crate::regex_to_dfa("((a|b)(a|b))*(a|b)b").unwrap();
+    let counts = (0..=4).map(|n| dfa.count_accepted(n)).collect::<Vec<u64>>();
+    assert_eq!(counts, vec![0, 0, 2, 0, 8]);
+    assert_eq!(dfa.count_accepted(64), 1 << 63);
+    assert_eq!(dfa.count_accepted(66), u64::MAX);
+}
+
+#[test]
+fn test_shortest_accepted() {
+    use crate::regex_to_dfa;
+    assert_eq!(regex_to_dfa("aaab*").unwrap().shortest_accepted(), Some("aaa".to_string()));
+    assert_eq!(regex_to_dfa("b*").unwrap().shortest_accepted(), Some(String::new()));
+    assert_eq!(regex_to_dfa("c|(a|b)b").unwrap().shortest_accepted(), Some("c".to_string()));
+    assert_eq!(DFA::empty_language(&"ab".chars().collect()).shortest_accepted(), None);
+}
+
+#[test]
+fn test_is_empty() {
+    // state 2 accepts, but nothing leads there
+    let mut dfa = DFA {
+        table: vec![
+            [('a', 1)].iter().copied().collect(),
+            [('a', 0)].iter().copied().collect(),
+            [('a', 0)].iter().copied().collect()
+        ],
+        accept_states: [2].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    assert!(dfa.is_empty());
+    dfa.accept_states.insert(1);
+    assert!(!dfa.is_empty());
+    assert!(!crate::regex_to_dfa("a*").unwrap().is_empty());
+}
+
+#[test]
+fn test_is_finite() {
+    use crate::regex_to_dfa;
+    assert!(!regex_to_dfa("a*").unwrap().is_finite());
+    assert!(regex_to_dfa("aa").unwrap().is_finite());
+    assert!(regex_to_dfa("a{2,5}|b?c").unwrap().is_finite());
+    assert!(!regex_to_dfa("ab+c").unwrap().is_finite());
+    assert!(DFA::empty_language(&"ab".chars().collect()).is_finite());
+
+    // a cycle that can't lead to an accept state doesn't count
+    let dfa = DFA {
+        table: vec![
+            [('a', 1), ('b', 2)].iter().copied().collect(),
+            HashMap::new(),
+            [('a', 2)].iter().copied().collect()
+        ],
+        accept_states: [1].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    assert!(dfa.is_finite());
+}
+
+#[cfg(feature = "rand")]
+#[test]
+fn test_sample() {
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(616);
+    for regex in &["ab*", "(a|b)*abb", "a{3}|b+c", "(ab|ba)*"] {
+        let dfa = crate::regex_to_dfa(regex).unwrap();
+        for _ in 0..100 {
+            let sample = dfa.sample(&mut rng, 8).unwrap();
+            assert!(sample.len() <= 8, "{} gave {:?}", regex, sample);
+            assert!(dfa.accepts(&sample), "{} gave {:?}", regex, sample);
+        }
+    }
+    assert_eq!(crate::regex_to_dfa("aaaa").unwrap().sample(&mut rng, 3), None);
+    assert_eq!(DFA::empty_language(&"ab".chars().collect()).sample(&mut rng, 10), None);
+}
+
+#[test]
+fn test_reachable_long_chain() {
+    // far deeper than a recursive traversal could go
+    let length = 60_000;
+    let mut table = (1..length as StateId)
+            .map(|next| [('a', next)].iter().copied().collect())
+            .collect::<Vec<DFATransitionMap>>();
+    table.push(HashMap::new());
+    let dfa = DFA {table, accept_states: [length as StateId - 1].iter().copied().collect(), alphabet: HashSet::new()};
+
+    assert_eq!(dfa.get_reachable().len(), length);
+    assert_eq!(dfa.get_coaccessible().len(), length);
+    assert!(dfa.accepts(&"a".repeat(length - 1)));
+}
+
+#[test]
+fn test_complement_minimal() {
+    use crate::regex_to_dfa;
+    let alphabet = "ab".chars().collect();
+    for (regex, states) in &[("a", 3), ("ab", 4), ("(a|b)*b", 2), ("(a|b)*", 1)] {
+        let dfa = regex_to_dfa(regex).unwrap();
+        let complement = dfa.complement(&alphabet);
+        let minimal = dfa.complement_minimal(&alphabet);
+        assert_eq!(minimal.table.len(), *states, "{}", regex);
+        for input in all_strings("ab", 5) {
+            assert_eq!(minimal.accepts(&input), !dfa.accepts(&input), "{} on {:?}", regex, input);
+            assert_eq!(minimal.accepts(&input), complement.accepts(&input), "{} on {:?}", regex, input);
+        }
+    }
+}
+
+#[test]
+fn test_state_frontier_sizes() {
+    // a 3-state cycle where `a` steps forward and `b` steps back
+    let dfa = DFA {
+        table: vec![
+            [('a', 1), ('b', 2)].iter().copied().collect(),
+            [('a', 2), ('b', 0)].iter().copied().collect(),
+            [('a', 0), ('b', 1)].iter().copied().collect()
+        ],
+        accept_states: [0].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    let alphabet = "ab".chars().collect();
+    assert_eq!(dfa.state_frontier_sizes(5, &alphabet), vec![1, 2, 3, 3, 3, 3]);
+
+    // with only `a` the frontier is always a single state
+    let alphabet = "a".chars().collect();
+    assert_eq!(dfa.state_frontier_sizes(3, &alphabet), vec![1, 1, 1, 1]);
+
+    // a chain runs out of states
+    let dfa = crate::regex_to_dfa("ab").unwrap();
+    let alphabet = "ab".chars().collect();
+    assert_eq!(dfa.state_frontier_sizes(3, &alphabet), vec![1, 1, 1, 0]);
+}
+
+#[test]
+fn test_multi_regex_to_dfa() {
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Token { Ident, Keyword, Number }
+    use Token::*;
+
+    let lexer = multi_regex_to_dfa(&[("a+", Ident), ("aa", Keyword), ("\\d+", Number)]).unwrap();
+    assert_eq!(lexer.longest_match("aa"), Some((2, Keyword))); // both match, keyword wins
+    assert_eq!(lexer.longest_match("a"), Some((1, Ident)));
+    assert_eq!(lexer.longest_match("aaa"), Some((3, Ident))); // longest match beats priority
+    assert_eq!(lexer.longest_match("aab"), Some((2, Keyword)));
+    assert_eq!(lexer.longest_match("42a"), Some((2, Number)));
+    assert_eq!(lexer.longest_match("b"), None);
+    assert_eq!(lexer.longest_match(""), None);
+
+    // patterns with loops at the start must not leak into each other
+    let lexer = multi_regex_to_dfa(&[("a*", Ident), ("b", Keyword)]).unwrap();
+    assert_eq!(lexer.longest_match("aab"), Some((2, Ident)));
+    assert_eq!(lexer.longest_match("b"), Some((1, Keyword)));
+    assert_eq!(lexer.longest_match("c"), Some((0, Ident)));
+}
+
+#[test]
+fn test_factors() {
+    use crate::regex_to_dfa;
+    let factors = regex_to_dfa("abc").unwrap().factors();
+    for accepted in &["", "a", "b", "c", "ab", "bc", "abc"] {
+        assert!(factors.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["ac", "ba", "cb", "abcabc", "d"] {
+        assert!(!factors.accepts(rejected), "should reject {:?}", rejected);
+    }
+
+    // loops survive, and factors of an infinite language
+    let factors = regex_to_dfa("x(ab)*y").unwrap().factors();
+    assert!(factors.accepts("babab"));
+    assert!(factors.accepts("aby"));
+    assert!(!factors.accepts("aa"));
+    assert!(!factors.accepts("yx"));
+}
+
+#[test]
+fn test_first_branch_point() {
+    use crate::regex_to_nfa;
+    let nfa = regex_to_nfa("ab|ac").unwrap();
+    let (position, state, targets) = nfa.first_branch_point("ab").unwrap();
+    assert_eq!((position, state), (0, 0));
+    assert_eq!(targets.len(), 2);
+
+    // alternatives share an output state, so identical branches collapse
+    assert_eq!(regex_to_nfa("(a|a)b").unwrap().first_branch_point("ab"), None);
+
+    // the branch is only found once the simulation reaches it
+    let nfa = regex_to_nfa("x(ab|ac)").unwrap();
+    assert_eq!(nfa.first_branch_point("x"), None);
+    assert_eq!(nfa.first_branch_point("xa").map(|(position, _, _)| position), Some(1));
+
+    assert_eq!(regex_to_nfa("abc").unwrap().first_branch_point("abc"), None);
+}
+
+#[test]
+fn test_nfa_accepts() {
+    for regex in &["ab*", "(a|b)*abb", "a?b?", "(a?b)+", "(ab|a)*b{1,2}", "a*|b+"] {
+        let nfa = crate::regex_to_nfa(regex).unwrap();
+        let dfa = nfa_to_dfa(&nfa);
+        for input in all_strings("abc", 5) {
+            assert_eq!(nfa.accepts(&input), dfa.accepts(&input), "{} on {:?}", regex, input);
+        }
+    }
+}
+
+#[test]
+fn test_epsilon_closure() {
+    let mut nfa = NFA::new();
+    for _ in 0..5 {
+        nfa.add_state();
+    }
+    nfa.add_epsilon(0, 1);
+    nfa.add_epsilon(1, 2);
+    nfa.add_epsilon(2, 3);
+    nfa.add_transition(3, 4, 'a');
+
+    let closure = |states: &[StateId]| nfa.epsilon_closure(&states.iter().copied().collect());
+    assert_eq!(closure(&[0]), [0, 1, 2, 3].iter().copied().collect());
+    assert_eq!(closure(&[2]), [2, 3].iter().copied().collect());
+    assert_eq!(closure(&[4]), [4].iter().copied().collect());
+    assert_eq!(closure(&[]), HashSet::new());
+}
+
+#[test]
+fn test_determinize() {
+    let tokens = crate::lexer::tokenize("(ab)*").unwrap();
+    let mut tree = crate::parser::tree();
+    crate::parser::parse(&tokens, &mut tree).unwrap();
+
+    let dfa = ast_to_nfa(&tree).determinize().minimize();
+    assert!(dfa.accepts("abab"));
+    assert!(!dfa.accepts("aba"));
+}
+
+#[test]
+fn test_with_alphabet() {
+    // accepts exactly "a"; nothing in the table mentions `b`
+    let dfa = DFA {
+        table: vec![[('a', 1)].iter().copied().collect(), HashMap::new()],
+        accept_states: [1].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    assert_eq!(dfa.alphabet(), "a".chars().collect());
+
+    let dfa = dfa.with_alphabet("ab".chars().collect()).unwrap();
+    assert_eq!(dfa.alphabet(), "ab".chars().collect());
+    let complement = dfa.complement(&dfa.alphabet());
+    assert!(complement.accepts(""));
+    assert!(complement.accepts("b"));
+    assert!(complement.accepts("ab"));
+    assert!(!complement.accepts("a"));
+
+    assert!(crate::regex_to_dfa("ab").unwrap().with_alphabet("b".chars().collect()).is_err());
+}
+
+#[test]
+fn test_run_steps() {
+    let dfa = DFA {
+        table: vec![
+            [('a', 1)].iter().copied().collect(),
+            [('b', 2)].iter().copied().collect(),
+            HashMap::new()
+        ],
+        accept_states: [2].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    assert_eq!(dfa.run_steps("ab"), vec![
+        Step {from: 0, symbol: 'a', to: Some(1), outcome: None},
+        Step {from: 1, symbol: 'b', to: Some(2), outcome: Some(true)}
+    ]);
+    assert_eq!(dfa.run_steps("a"), vec![
+        Step {from: 0, symbol: 'a', to: Some(1), outcome: Some(false)}
+    ]);
+
+    // stops at the first undefined transition
+    assert_eq!(dfa.run_steps("bab"), vec![
+        Step {from: 0, symbol: 'b', to: None, outcome: Some(false)}
+    ]);
+    assert!(dfa.run_steps("").is_empty());
+}
+
+#[test]
+fn test_estimated_dfa_bound() {
+    let nfa = crate::regex_to_nfa("ab").unwrap();
+    assert_eq!(nfa.table.len(), 3);
+    assert_eq!(nfa.estimated_dfa_bound(), 8);
+    assert_eq!(explosion_warning(&nfa), None);
+
+    let nfa = crate::regex_to_nfa(&"ab".repeat(10)).unwrap();
+    assert_eq!(nfa.estimated_dfa_bound(), 1 << 21);
+    assert_eq!(explosion_warning(&nfa).unwrap(), "NFA has 21 states, so the DFA could have up to 2097152 states");
+
+    let nfa = crate::regex_to_nfa(&"a".repeat(100)).unwrap();
+    assert_eq!(nfa.estimated_dfa_bound(), usize::MAX);
+}
+
+#[test]
+fn test_lexicographic_iter() {
+    use crate::regex_to_dfa;
+    let alphabet = ['b', 'a'];
+    let strings = regex_to_dfa("a*b*").unwrap().lexicographic_iter(&alphabet).take(4).collect::<Vec<String>>();
+    assert_eq!(strings, vec!["", "a", "aa", "aaa"]);
+
+    // finite languages are listed completely
+    let strings = regex_to_dfa("b|ab|a(a|b)").unwrap().lexicographic_iter(&alphabet).collect::<Vec<String>>();
+    assert_eq!(strings, vec!["aa", "ab", "b"]);
+
+    // strictly increasing, even though the lengths aren't
+    let strings = regex_to_dfa("(ab|b)*").unwrap().lexicographic_iter(&alphabet).take(20).collect::<Vec<String>>();
+    assert!(strings.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(&strings[..4], &["", "ab", "abab", "ababab"]);
+
+    assert_eq!(regex_to_dfa("c").unwrap().lexicographic_iter(&alphabet).next(), None);
+}
+
+#[test]
+fn test_transition_monoid_size() {
+    // counting `a`s mod 2: the identity and the swap
+    let dfa = DFA {
+        table: vec![[('a', 1)].iter().copied().collect(), [('a', 0)].iter().copied().collect()],
+        accept_states: [0].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    assert_eq!(dfa.transition_monoid_size(), 2);
+
+    // "last symbol was `a`": the identity and two constant maps
+    let dfa = DFA {
+        table: vec![
+            [('a', 1), ('b', 0)].iter().copied().collect(),
+            [('a', 1), ('b', 0)].iter().copied().collect()
+        ],
+        accept_states: [1].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    assert_eq!(dfa.transition_monoid_size(), 3);
+
+    // undefined transitions: "", "a", and everything longer falls off
+    let dfa = crate::regex_to_dfa("a").unwrap();
+    assert_eq!(dfa.transition_monoid_size(), 3);
+}
+
+#[cfg(test)]
+fn graph_nodes(graph: &str) -> HashSet<StateId> {
+    graph.split(|c: char| !c.is_ascii_digit())
+            .filter_map(|id| id.parse().ok())
+            .collect()
+}
+
+#[test]
+fn test_to_graph_full() {
+    // state 2 is unreachable but leads into the machine; state 3 is isolated
+    let dfa = DFA {
+        table: vec![
+            [('a', 1)].iter().copied().collect(),
+            HashMap::new(),
+            [('b', 1)].iter().copied().collect(),
+            HashMap::new()
+        ],
+        accept_states: [1].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    let reachable: HashSet<StateId> = [0, 1].iter().copied().collect();
+    let all: HashSet<StateId> = [0, 1, 2, 3].iter().copied().collect();
+    assert_eq!(graph_nodes(&dfa.to_graph()), reachable);
+    assert_eq!(graph_nodes(&dfa.to_graph_full()), all);
+
+    let full = dfa.to_graph_full();
+    assert!(full.contains("2 -> 1 [label=\"b\"];\n"));
+    assert!(full.contains("    2 [style=dashed];\n    3 [style=dashed];\n"));
+    assert!(!full.contains("0 [style=dashed]"));
+}
+
+#[test]
+fn test_cyclic_shifts() {
+    use crate::regex_to_dfa;
+    let shifts = regex_to_dfa("abc").unwrap().cyclic_shifts().determinize();
+    for accepted in &["abc", "bca", "cab"] {
+        assert!(shifts.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["", "acb", "bac", "ab", "abcabc"] {
+        assert!(!shifts.accepts(rejected), "should reject {:?}", rejected);
+    }
+
+    // compare against rotating every accepted string by brute force
+    for regex in &["ab*", "(ab|c)*", "a*b*", "a(ba)*"] {
+        let dfa = regex_to_dfa(regex).unwrap();
+        let shifts = dfa.cyclic_shifts().determinize();
+        for input in all_strings("abc", 5) {
+            let expected = (0..input.len().max(1))
+                    .any(|i| dfa.accepts(&format!("{}{}", &input[i..], &input[..i])));
+            assert_eq!(shifts.accepts(&input), expected, "{} on {:?}", regex, input);
+        }
+    }
+    assert!(!regex_to_dfa("a").unwrap().cyclic_shifts().determinize().accepts(""));
+}
+
+#[test]
+#[should_panic(expected = "past the limit of 65536 states")]
+fn test_cyclic_shifts_too_many_states() {
+    // 2 * 200^2 copies can't all be numbered by a StateId
+    crate::regex_to_dfa(&"a".repeat(199)).unwrap().cyclic_shifts();
+}
+
+#[test]
+fn test_minimal_dfa() {
+    // build (a|b)*a one edit at a time, checking against a from-scratch minimization
+    let mut editor = MinimalDfa::new(DFA {table: vec![HashMap::new()], accept_states: HashSet::new(), alphabet: HashSet::new()});
+    let mut reference = editor.dfa.clone();
+    let check = |editor: &MinimalDfa, reference: &DFA| {
+        let minimal = reference.minimize();
+        assert_eq!(editor.state_count(), minimal.table.len());
+        for input in all_strings("ab", 4) {
+            assert_eq!(editor.accepts(&input), minimal.accepts(&input), "{:?}", input);
+        }
+    };
+    check(&editor, &reference);
+
+    let state = editor.add_state();
+    reference.add_state();
+    editor.set_accept(state, true);
+    reference.set_accept(state, true);
+    check(&editor, &reference); // accept state still unreachable
+    assert_eq!(editor.state_count(), 1);
+
+    for (from, to, label) in &[(0, 1, 'a'), (0, 0, 'b'), (1, 1, 'a'), (1, 0, 'b')] {
+        editor.add_transition(*from, *to, *label);
+        reference.add_transition(*from, *to, *label);
+        check(&editor, &reference);
+    }
+    assert!(editor.accepts("aba"));
+    assert!(!editor.accepts("ab"));
+    assert_eq!(editor.state_count(), 2);
+
+    // making everything accept collapses to one state
+    editor.set_accept(0, true);
+    assert_eq!(editor.state_count(), 1);
+    assert!(editor.accepts("ab"));
+}
+
+#[test]
+fn test_dfa_rejecting_words() {
+    let alphabet = "acst".chars().collect();
+    let dfa = dfa_rejecting_words(&["cat", "cast", "at"], &alphabet);
+    for rejected in &["cat", "cast", "at"] {
+        assert!(!dfa.accepts(rejected), "should reject {:?}", rejected);
+    }
+    for accepted in &["", "c", "ca", "cats", "casts", "a", "ats", "tac"] {
+        assert!(dfa.accepts(accepted), "should accept {:?}", accepted);
+    }
+    assert!(!dfa.accepts("dog")); // not over the alphabet
+
+    // nothing blocked accepts everything
+    assert_eq!(dfa_rejecting_words(&[], &alphabet).table.len(), 1);
+}
+
+#[test]
+fn test_run_to_json() {
+    let dfa = crate::regex_to_dfa("ab*").unwrap();
+    let record: RunRecord = serde_json::from_str(&dfa.run_to_json("abb")).unwrap();
+    assert_eq!(record.input, "abb");
+    assert!(record.accepted);
+    assert_eq!(record.states.len(), 4);
+    assert_eq!(record.states[0], 0);
+    assert_eq!(record.stuck_at, None);
+
+    let record: RunRecord = serde_json::from_str(&dfa.run_to_json("aba")).unwrap();
+    assert!(!record.accepted);
+    assert_eq!(record.states.len(), 3);
+    assert_eq!(record.stuck_at, Some(2));
+
+    let record: RunRecord = serde_json::from_str(&dfa.run_to_json("")).unwrap();
+    assert!(!record.accepted);
+    assert_eq!(record.states, vec![0]);
+}
+
+#[test]
+fn test_is_aperiodic() {
+    use crate::regex_to_dfa;
+    for regex in &["a*", "ab", "(ab)*", "a*b*", "(a|b)*a"] {
+        assert!(regex_to_dfa(regex).unwrap().is_aperiodic(), "{} should be aperiodic", regex);
+    }
+    for regex in &["(aa)*", "a(aa)*", "(aaa)*b", "((a|b)(a|b))*"] {
+        assert!(!regex_to_dfa(regex).unwrap().is_aperiodic(), "{} should not be aperiodic", regex);
+    }
+}
+
+#[test]
+fn test_separating_sequence() {
+    // 1 and 2 both go on `a` to accepting state 3, but only 2 accepts `b`
+    let dfa = DFA {
+        table: vec![
+            [('a', 1), ('b', 2)].iter().copied().collect(),
+            [('a', 3), ('b', 1)].iter().copied().collect(),
+            [('a', 3), ('b', 3)].iter().copied().collect(),
+            HashMap::new()
+        ],
+        accept_states: [3].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    let alphabet = "ab".chars().collect();
+    assert_eq!(dfa.separating_sequence(1, 2, &alphabet), Some("b".to_string()));
+    assert_eq!(dfa.separating_sequence(0, 3, &alphabet), Some("".to_string()));
+    assert_eq!(dfa.separating_sequence(0, 1, &alphabet), Some("a".to_string()));
+    assert_eq!(dfa.separating_sequence(2, 2, &alphabet), None);
+
+    // equivalent states that minimization would merge
+    let dfa = crate::regex_to_dfa("a|b").unwrap();
+    assert_eq!(dfa.separating_sequence(dfa.get(0)[&'a'], dfa.get(0)[&'b'], &alphabet), None);
+}
+
+#[test]
+fn test_universal_and_empty_language() {
+    let alphabet = "ab".chars().collect();
+    let universal = DFA::universal(&alphabet);
+    let empty = DFA::empty_language(&alphabet);
+    for input in all_strings("ab", 4) {
+        assert!(universal.accepts(&input));
+        assert!(!empty.accepts(&input));
+    }
+    assert!(!universal.accepts("c"));
+    assert_eq!(universal.alphabet(), alphabet);
+    assert_eq!(empty.alphabet(), alphabet);
+
+    // identity and zero for intersection
+    let dfa = crate::regex_to_dfa("ab*").unwrap();
+    assert!(dfa.intersects(&universal, &alphabet));
+    assert!(!dfa.intersects(&empty, &alphabet));
+    assert!(!empty.intersects(&universal, &alphabet));
+    assert!(dfa.intersect(&universal).equivalent(&dfa));
+    assert!(dfa.intersect(&empty).equivalent(&empty));
+    assert_eq!(empty.complement(&alphabet).minimize().table.len(), universal.minimize().table.len());
+}
+
+#[test]
+fn test_hamming_ball() {
+    use crate::regex_to_dfa;
+    let alphabet = "abxy".chars().collect();
+    let ball = regex_to_dfa("ab").unwrap().hamming_ball(1, &alphabet).determinize();
+    for accepted in &["ab", "xb", "ax", "bb", "aa"] {
+        assert!(ball.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["xy", "ba", "a", "abx", ""] {
+        assert!(!ball.accepts(rejected), "should reject {:?}", rejected);
+    }
+
+    // radius 0 is the language itself
+    let dfa = regex_to_dfa("a(b|x)*").unwrap();
+    let ball = dfa.hamming_ball(0, &alphabet).determinize();
+    for input in all_strings("abxy", 3) {
+        assert_eq!(ball.accepts(&input), dfa.accepts(&input), "{:?}", input);
+    }
+    let ball = dfa.hamming_ball(2, &alphabet).determinize();
+    assert!(ball.accepts("ybxy"));
+    assert!(!ball.accepts("yyyb"));
+}
+
+#[test]
+#[should_panic(expected = "past the limit of 65536 states")]
+fn test_hamming_ball_too_many_states() {
+    // 401 copies of a 200-state DFA can't all be numbered by a StateId
+    crate::regex_to_dfa(&"a".repeat(199)).unwrap().hamming_ball(400, &"ab".chars().collect());
+}
+
+#[test]
+fn test_matcher_from_reader() {
+    let yaml = "
+patterns:
+  - name: greeting
+    regex: hi+
+  - name: short
+    regex: \\w\\w
+  - name: number
+    regex: \\d+
+";
+    let matcher = matcher_from_reader(yaml.as_bytes()).unwrap();
+    assert_eq!(matcher.all_matches("hi"), vec!["greeting", "short"]);
+    assert_eq!(matcher.all_matches("hiii"), vec!["greeting"]);
+    assert_eq!(matcher.all_matches("ok"), vec!["short"]);
+    assert_eq!(matcher.all_matches("42"), vec!["number"]);
+    assert!(matcher.all_matches("h").is_empty());
+
+    assert!(matcher_from_reader("patterns: [{name: oops}]".as_bytes()).is_err());
+}
+
+#[test]
+fn test_accepts_as_subsequence() {
+    let dfa = crate::regex_to_dfa("abc").unwrap();
+    assert!(dfa.accepts_as_subsequence("abc"));
+    assert!(dfa.accepts_as_subsequence("axbyc"));
+    assert!(dfa.accepts_as_subsequence("aabbcc"));
+    assert!(!dfa.accepts_as_subsequence("acb"));
+    assert!(!dfa.accepts_as_subsequence("ab"));
+
+    // the empty subsequence counts too
+    assert!(crate::regex_to_dfa("a*").unwrap().accepts_as_subsequence("xyz"));
+}
+
+#[test]
+fn test_to_mermaid() {
+    let dfa = crate::regex_to_dfa("ab*").unwrap();
+    assert_eq!(dfa.to_mermaid(),
+"stateDiagram-v2
+    [*] --> q0
+    q0 --> q1: a
+    q1 --> q1: b
+    q1 --> [*]
+");
+}
+
+#[test]
+fn test_longest_rejected() {
+    use crate::regex_to_dfa;
+    let alphabet = "ab".chars().collect();
+    assert_eq!(regex_to_dfa("a*").unwrap().longest_rejected(&alphabet), None);
+    assert_eq!(regex_to_dfa("(a|b)(a|b)(a|b)*").unwrap().longest_rejected(&alphabet), Some("a".to_string()));
+    assert_eq!(regex_to_dfa("(a|b)*").unwrap().longest_rejected(&alphabet), None);
+
+    // everything except "", "a" and "ab"
+    let dfa = regex_to_dfa("b(a|b)*|aa(a|b)*|ab(a|b)(a|b)*").unwrap();
+    assert_eq!(dfa.longest_rejected(&alphabet), Some("ab".to_string()));
+
+    // a long chain of states, deeper than a recursive search could go
+    let n = 20000;
+    let table = (0..=n).map(|state| [('a', state_id(n.min(state + 1)))].iter().copied().collect()).collect();
+    let dfa = DFA {table, accept_states: [state_id(n)].iter().copied().collect(), alphabet: HashSet::new()};
+    assert_eq!(dfa.longest_rejected(&"a".chars().collect()), Some("a".repeat(n - 1)));
+}
+
+#[test]
+fn test_accepts_with_fold() {
+    let dfa = crate::regex_to_dfa("abc").unwrap();
+    assert!(!dfa.accepts("ABC"));
+    assert!(dfa.accepts_with_fold("ABC", |letter| letter.to_ascii_lowercase()));
+    assert!(dfa.accepts_with_fold("aBc", |letter| letter.to_ascii_lowercase()));
+    assert!(!dfa.accepts_with_fold("ABD", |letter| letter.to_ascii_lowercase()));
+
+    // fold several symbols into one
+    let dfa = crate::regex_to_dfa("0+").unwrap();
+    assert!(dfa.accepts_with_fold("1905", |letter| if letter.is_ascii_digit() { '0' } else { letter }));
+}
+
+#[test]
+fn test_enumerate() {
+    let dfa = crate::regex_to_dfa("a*b|c").unwrap();
+    assert_eq!(dfa.enumerate(3), vec!["b", "c", "ab", "aab"]);
+    assert_eq!(dfa.enumerate(0), Vec::<String>::new());
+    assert_eq!(crate::regex_to_dfa("a*").unwrap().enumerate(2), vec!["", "a", "aa"]);
+}
+
+#[test]
+fn test_commutation_closure() {
+    use crate::regex_to_dfa;
+    let closure = regex_to_dfa("ab").unwrap().commutation_closure(&[('a', 'b')], 5).determinize();
+    assert!(closure.accepts("ab"));
+    assert!(closure.accepts("ba"));
+    assert!(!closure.accepts("aa"));
+
+    // c doesn't commute with anything, so it stays put
+    let closure = regex_to_dfa("abc(a|b)").unwrap().commutation_closure(&[('b', 'a')], 5).determinize();
+    for accepted in &["abca", "bacb", "abcb", "baca"] {
+        assert!(closure.accepts(accepted), "should accept {:?}", accepted);
+    }
+    assert!(!closure.accepts("acba"));
+    assert!(!closure.accepts("cab"));
+}
+
+#[test]
+fn test_accepts_any_of() {
+    let dfa = crate::regex_to_dfa("ab*").unwrap();
+    assert!(dfa.accepts_any_of(&["b", "abb", "ba"]));
+    assert!(!dfa.accepts_any_of(&["b", "ba", ""]));
+    assert!(!dfa.accepts_any_of(&[]));
+
+    // stops at the first match
+    assert_eq!(dfa.first_accepted(&["b", "a", "ab"]), Some(1));
+    assert_eq!(dfa.first_accepted(&["b"]), None);
+}
+
+#[test]
+fn test_with_parity_constraint() {
+    use crate::regex_to_dfa;
+    let even = regex_to_dfa("(a|b)*").unwrap().with_parity_constraint('a', true);
+    for accepted in &["", "aa", "b", "abab", "bbaab"] {
+        assert!(even.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["a", "ab", "aaa", "c"] {
+        assert!(!even.accepts(rejected), "should reject {:?}", rejected);
+    }
+
+    let odd = regex_to_dfa("a*b").unwrap().with_parity_constraint('a', false);
+    assert!(odd.accepts("ab"));
+    assert!(odd.accepts("aaab"));
+    assert!(!odd.accepts("aab"));
+    assert!(!odd.accepts("a"));
+}
+
+#[test]
+fn test_useless_transitions() {
+    // 2 is a dead sink and 3 is unreachable
+    let dfa = DFA {
+        table: vec![
+            [('a', 1), ('b', 2)].iter().copied().collect(),
+            [('a', 2)].iter().copied().collect(),
+            [('a', 2), ('b', 2)].iter().copied().collect(),
+            [('a', 1)].iter().copied().collect()
+        ],
+        accept_states: [1].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    assert_eq!(dfa.useless_transitions(), vec![(0, 'b', 2), (1, 'a', 2), (2, 'a', 2), (2, 'b', 2), (3, 'a', 1)]);
+    assert!(crate::regex_to_dfa("ab*").unwrap().useless_transitions().is_empty());
+}
+
+#[test]
+fn test_grade_against() {
+    let alphabet = "ab".chars().collect();
+    let reference = crate::regex_to_dfa("ab*").unwrap().minimize();
+
+    // ab* with the b loop unrolled once
+    let student = DFA {
+        table: vec![
+            [('a', 1)].iter().copied().collect(),
+            [('b', 2)].iter().copied().collect(),
+            [('b', 3)].iter().copied().collect(),
+            [('b', 2)].iter().copied().collect()
+        ],
+        accept_states: [1, 2, 3].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    let report = student.grade_against(&reference, &alphabet);
+    assert!(report.equivalent);
+    assert_eq!(report.to_string(), "equivalent, not minimal, +2 states");
+
+    let report = crate::regex_to_dfa("ab*a").unwrap().grade_against(&reference, &alphabet);
+    assert_eq!(report.counterexample, Some("a".to_string()));
+    assert_eq!(report.to_string(), "not equivalent (differs on \"a\"), minimal, +1 states");
+
+    // a complete minimal DFA with a trap state is still minimal
+    let mut complete = reference.clone();
+    complete.complete(&alphabet);
+    let report = complete.grade_against(&reference, &alphabet);
+    assert_eq!(report.to_string(), "equivalent, minimal, +1 states");
+}
+
+#[test]
+fn test_complete() {
+    let alphabet: HashSet<char> = "abc".chars().collect();
+    let mut dfa = crate::regex_to_dfa("ab*").unwrap();
+    let before = dfa.enumerate(4);
+
+    dfa.complete(&alphabet);
+    for state in dfa.get_reachable() {
+        assert_eq!(dfa.get(state).len(), alphabet.len());
+    }
+    assert_eq!(dfa.enumerate(4), before);
+
+    // already complete, so no second trap state
+    let num_states = dfa.num_states();
+    dfa.complete(&alphabet);
+    assert_eq!(dfa.num_states(), num_states);
+}
+
+#[test]
+fn test_prune_dead() {
+    // 2 and 3 loop forever without accepting
+    let dfa = DFA {
+        table: vec![
+            [('a', 1), ('b', 2)].iter().copied().collect(),
+            [('a', 1), ('b', 4)].iter().copied().collect(),
+            [('a', 3)].iter().copied().collect(),
+            [('a', 2)].iter().copied().collect(),
+            [('a', 1)].iter().copied().collect()
+        ],
+        accept_states: [1].iter().copied().collect(),
+        alphabet: HashSet::new()
+    };
+    let pruned = dfa.prune_dead();
+    assert_eq!(pruned.num_states(), 3);
+    assert_eq!(pruned.alphabet(), dfa.alphabet());
+    assert!(pruned.useless_transitions().is_empty());
+    assert_eq!(pruned.enumerate(6), dfa.enumerate(6));
+    assert!(!pruned.accepts("ba") && !pruned.accepts("bb"));
+
+    // the start state stays even when it's dead
+    let pruned = DFA::empty_language(&dfa.alphabet()).prune_dead();
+    assert_eq!(pruned.num_states(), 1);
+    assert!(pruned.is_empty());
+}
+
+#[test]
+fn test_to_yaml() {
+    let dfa = crate::regex_to_dfa("a(b|c)*").unwrap();
+    let yaml = dfa.to_yaml();
+
+    // read it back in with hw2 and hw3's loader
+    let loaded = automata_core::DFA::from_reader(yaml.as_bytes()).unwrap();
+    assert!(loaded.validate().is_ok());
+    assert_eq!(loaded.alphabet, vec!["a", "b", "c"]);
+    assert_eq!(loaded.n_states as usize, dfa.num_states() + 1);
+    for input in &["", "a", "ab", "acbc", "b", "aa", "abca", "ba"] {
+        assert_eq!(loaded.run(input, false), dfa.accepts(input), "{}", input);
+    }
+
+    // symbols YAML treats specially are quoted
+    let dfa = crate::regex_to_dfa("\\*\\[").unwrap();
+    let loaded = automata_core::DFA::from_reader(dfa.to_yaml().as_bytes()).unwrap();
+    assert_eq!(loaded.alphabet, vec!["*", "["]);
+    assert!(loaded.run("*[", false));
+}
+
+#[test]
+fn test_to_json() {
+    let dfa = crate::regex_to_dfa("a(b|c)*").unwrap();
+    let json = dfa.to_json();
+    let record: GraphRecord = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(record.start, 0);
+    assert_eq!(record.accept, {
+        let mut accept = dfa.accept_states.iter().copied().collect::<Vec<StateId>>();
+        accept.sort_unstable();
+        accept
+    });
+    let edges = record.transitions.iter()
+            .map(|trans| (trans.from, trans.to, trans.symbol))
+            .collect::<HashSet<crate::graphviz::Edge>>();
+    assert_eq!(edges, dfa.edges().into_iter().collect());
+
+    assert_eq!(crate::regex_to_dfa("a").unwrap().to_json(),
+            r#"{"start":0,"accept":[1],"transitions":[{"from":0,"symbol":"a","to":1}]}"#);
+}
+
+#[test]
+fn test_to_table() {
+    assert_eq!(crate::regex_to_dfa("ab").unwrap().to_table(),
+"      | a  | b
+\u{2192}  q0 | q1 |
+   q1 |    | q2
+ * q2 |    |");
+
+    // the start state can accept too
+    let table = crate::regex_to_minimal_dfa("a*").unwrap().to_table();
+    assert_eq!(table, "      | a\n\u{2192}* q0 | q0");
+}
@@ -4,32 +4,78 @@ pub enum Token {
     Letter(char),        // a-z, 0-9, space
     Group(Vec<Token>),   // (...)
     Union, Star, Plus,   // |, *, +
-    AnyLetter, AnyDigit  // \w, \d
+    Optional,            // ?
+    RepeatRange(usize, Option<usize>), // {n}, {n,}, {n,m}
+    AnyLetter, AnyDigit, // \w, \d
+    CharSet(Vec<char>),  // [...]
+    NegatedCharSet(Vec<char>) // [^...], or !x for [^x]
 }
 
 /// Converts a raw string into a sequence of tokens
-pub fn tokenize(input: &str) -> Vec<Token> {
+/// Fails if the string contains an unrecognized character or escape sequence,
+/// a malformed repetition or character set, a `!` not followed by a letter, or
+/// unbalanced parentheses.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    tokenize_at(input, 0)
+}
+
+/// Tokenize a substring that starts `offset` chars into the whole regex, so
+/// error positions are reported relative to the whole regex
+fn tokenize_at(input: &str, offset: usize) -> Result<Vec<Token>, String> {
+    let position = |i: usize| offset + input[..i].chars().count();
     let mut tokens = vec![];
     let mut unmatched_parens = 0;
     let mut group_start = 0;
+    let mut range_start = None;
+    let mut set_start = None;
     let mut escaped = false;
-    for (i, chr) in input.chars().enumerate() {
+    let mut negated = false;
+    for (i, chr) in input.char_indices() {
         match chr {
-            _ if escaped => tokens.push(escape_seq(chr)),
+            _ if escaped => if unmatched_parens == 0 { tokens.push(escape_seq(chr)?) },
+            '}' if unmatched_parens == 0 && range_start.is_some() => {
+                tokens.push(repeat_range(&input[range_start.unwrap()..i])?);
+                range_start = None;
+            },
+            _ if range_start.is_some() => (),
+            ']' if unmatched_parens == 0 && set_start.is_some() => {
+                tokens.push(char_set(&input[set_start.unwrap()..i])?);
+                set_start = None;
+            },
+            _ if set_start.is_some() => (),
+            _ if negated => if unmatched_parens == 0 { tokens.push(negated_letter(chr)?) },
+            '{' if unmatched_parens == 0 => range_start = Some(i + 1),
+            '[' if unmatched_parens == 0 => set_start = Some(i + 1),
             '(' => {
                 unmatched_parens += 1;
                 if unmatched_parens == 1 { group_start = i + 1 }
             },
+            ')' if unmatched_parens == 0 => return Err(format!("Unmatched `)` at position {}", position(i))),
             ')' => {
                 unmatched_parens -= 1;
-                if unmatched_parens == 0 { tokens.push(group(&input[group_start..i])) }
+                if unmatched_parens == 0 {
+                    tokens.push(Token::Group(tokenize_at(&input[group_start..i], position(group_start))?));
+                }
             },
-            '\\' => (),
-            _ => if unmatched_parens == 0 { tokens.push(token(chr)) }
+            '\\' | '!' => (),
+            _ => if unmatched_parens == 0 { tokens.push(token(chr)?) }
         }
-        escaped = chr == '\\';
+        negated = !escaped && chr == '!';
+        escaped = !escaped && chr == '\\';
+    }
+    if unmatched_parens > 0 {
+        return Err(format!("Unclosed `(` starting at position {}", position(group_start - 1)));
+    }
+    if negated {
+        return Err("Expected a letter after `!`".to_string());
     }
-    tokens
+    if range_start.is_some() {
+        return Err("Unclosed `{`".to_string());
+    }
+    if set_start.is_some() {
+        return Err("Unclosed `[`".to_string());
+    }
+    Ok(tokens)
 }
 
 /// Find the first index of a token
@@ -49,53 +95,199 @@ pub fn find_adjacent_values(tokens: &[Token]) -> Option<(usize, usize)> {
             }).map(|(i, _)| (i, i + 1))
 }    
 
+/// Find the first bounded repetition, returning its index and bounds
+pub fn find_repeat_range(tokens: &[Token]) -> Option<(usize, usize, Option<usize>)> {
+    tokens.iter().enumerate()
+            .find_map(|(i, t)| match *t {
+                Token::RepeatRange(min, max) => Some((i, min, max)),
+                _ => None
+            })
+}
+
 impl Token {
     /// Is this token a value (i.e. not an operator)?
     pub fn is_value(&self) -> bool {
         use Token::*;
-        match self {
-            Letter(_) | Group(_) | AnyLetter | AnyDigit => true,
-            _ => false
-        }
+        matches!(self, Letter(_) | Group(_) | AnyLetter | AnyDigit | CharSet(_) | NegatedCharSet(_))
     }
 
     /// Is this token a left-value (i.e. not a binary or left unary operator)?
     pub fn is_left_value(&self) -> bool {
         use Token::*;
-        match self {
-            Union => false,
-            _ => true
-        }
+        !matches!(self, Union)
     }
 }
 
 /// Convert a character into a token
-fn token(chr: char) -> Token {
+fn token(chr: char) -> Result<Token, String> {
     match chr {
-        '|' => Token::Union,
-        '*' => Token::Star,
-        '+' => Token::Plus,
-        'a'..='z' | '0'..='9' | ' ' => Token::Letter(chr),
-        _ => {
-            eprintln!("Unrecognized character `{}`", chr);
-            std::process::exit(1);
-        }
+        '|' => Ok(Token::Union),
+        '*' => Ok(Token::Star),
+        '+' => Ok(Token::Plus),
+        '?' => Ok(Token::Optional),
+        _ if is_letter(chr) => Ok(Token::Letter(chr)),
+        _ => Err(format!("Unrecognized character `{}`", chr))
     }
 }
 
 /// Parse an escape sequence
-fn escape_seq(chr: char) -> Token {
+fn escape_seq(chr: char) -> Result<Token, String> {
+    match chr {
+        'w' => Ok(Token::AnyLetter),
+        'd' => Ok(Token::AnyDigit),
+        _ if is_metachar(chr) => Ok(Token::Letter(chr)),
+        _ => Err(format!("Unrecognized escape sequence `\\{}`", chr))
+    }
+}
+
+/// Parse the letter following a `!`, which is shorthand for `[^x]`
+fn negated_letter(chr: char) -> Result<Token, String> {
     match chr {
-        'w' => Token::AnyLetter,
-        'd' => Token::AnyDigit,
-        _ => {
-            eprintln!("Unrecognized escape sequence `\\{}`", chr);
-            std::process::exit(1);
+        _ if is_letter(chr) => Ok(Token::NegatedCharSet(vec![chr])),
+        _ => Err(format!("Expected a letter after `!`, found `{}`", chr))
+    }
+}
+
+/// Parse the inside of a bounded repetition, e.g. `2,3` from `{2,3}`
+fn repeat_range(spec: &str) -> Result<Token, String> {
+    let malformed = || format!("Malformed repetition `{{{}}}`", spec);
+    let bound = |bound: &str| bound.trim().parse::<usize>().map_err(|_| malformed());
+    let (min, max) = match spec.split_once(',') {
+        None => (bound(spec)?, Some(bound(spec)?)),
+        Some((min, max)) if max.trim().is_empty() => (bound(min)?, None),
+        Some((min, max)) => (bound(min)?, Some(bound(max)?))
+    };
+    match max {
+        Some(max) if max < min => Err(format!("Repetition `{{{}}}` has a minimum larger than its maximum", spec)),
+        _ => Ok(Token::RepeatRange(min, max))
+    }
+}
+
+/// Parse the inside of a bracket expression, e.g. `a-f0-3xyz` from `[a-f0-3xyz]`
+/// Returns the members in sorted order without duplicates. A leading `^`
+/// negates the set.
+fn char_set(spec: &str) -> Result<Token, String> {
+    let negated = spec.starts_with('^');
+    let chars = spec.trim_start_matches('^').chars().collect::<Vec<char>>();
+    let mut members = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let start = chars[i];
+        if !is_letter(start) {
+            return Err(format!("Unrecognized character `{}` in `[{}]`", start, spec));
+        }
+        match chars.get(i + 1..i + 3) {
+            Some(&['-', end]) => {
+                if !is_letter(end) || end < start {
+                    return Err(format!("Invalid range `{}-{}` in `[{}]`", start, end, spec));
+                }
+                members.extend(start..=end);
+                i += 3;
+            },
+            _ => {
+                members.push(start);
+                i += 1;
+            }
         }
     }
+    if members.is_empty() {
+        return Err(format!("Empty character set `[{}]`", spec));
+    }
+    members.sort_unstable();
+    members.dedup();
+    Ok(if negated { Token::NegatedCharSet(members) } else { Token::CharSet(members) })
+}
+
+/// Is this character allowed as a literal letter?
+fn is_letter(chr: char) -> bool {
+    matches!(chr, 'a'..='z' | '0'..='9' | ' ')
+}
+
+/// Get every character allowed as a literal letter
+pub fn letters() -> impl Iterator<Item = char> {
+    ('a'..='z').chain('0'..='9').chain(Some(' '))
+}
+
+/// Does this character have to be escaped to match it literally?
+pub fn is_metachar(chr: char) -> bool {
+    "|*+?(){}[]\\!".contains(chr)
+}
+
+/// Split sorted characters into single characters and `lo-hi` ranges,
+/// e.g. `a`, `c-f` (a run needs at least three characters to be a range)
+pub fn ranges(symbols: &[char]) -> Vec<String> {
+    let mut parts = vec![];
+    let mut i = 0;
+    while i < symbols.len() {
+        // find the end of this run of consecutive characters
+        let mut j = i;
+        while j + 1 < symbols.len() && symbols[j + 1] as u32 == symbols[j] as u32 + 1 {
+            j += 1;
+        }
+        if j - i >= 2 {
+            parts.push(format!("{}-{}", symbols[i], symbols[j]));
+        } else {
+            parts.extend(symbols[i..=j].iter().map(char::to_string));
+        }
+        i = j + 1;
+    }
+    parts
+}
+
+#[test]
+fn test_repeat_range() {
+    assert_eq!(repeat_range("3"), Ok(Token::RepeatRange(3, Some(3))));
+    assert_eq!(repeat_range("2,"), Ok(Token::RepeatRange(2, None)));
+    assert_eq!(repeat_range("2,3"), Ok(Token::RepeatRange(2, Some(3))));
+    assert!(repeat_range("2,1").is_err());
+    assert!(repeat_range(",").is_err());
+    assert!(repeat_range(",3").is_err());
+    assert!(repeat_range("").is_err());
+    assert!(repeat_range("a").is_err());
+    assert_eq!(tokenize("(ab){2}c").unwrap(), vec![
+        Token::Group(vec![Token::Letter('a'), Token::Letter('b')]),
+        Token::RepeatRange(2, Some(2)),
+        Token::Letter('c')
+    ]);
+}
+
+#[test]
+fn test_char_set() {
+    assert_eq!(char_set("a-c0-1x"), Ok(Token::CharSet(vec!['0', '1', 'a', 'b', 'c', 'x'])));
+    assert_eq!(char_set("a-a"), Ok(Token::CharSet(vec!['a'])));
+    assert_eq!(char_set("ba-b"), Ok(Token::CharSet(vec!['a', 'b'])));
+    assert!(char_set("").is_err());
+    assert!(char_set("c-a").is_err());
+    assert!(char_set("a-").is_err());
+    assert!(char_set("A").is_err());
+    assert_eq!(char_set("^0-2"), Ok(Token::NegatedCharSet(vec!['0', '1', '2'])));
+    assert!(char_set("^").is_err());
+}
+
+#[test]
+fn test_escape_seq() {
+    use Token::*;
+    assert_eq!(tokenize("a\\*b").unwrap(), vec![Letter('a'), Letter('*'), Letter('b')]);
+    assert_eq!(tokenize("\\\\\\w").unwrap(), vec![Letter('\\'), AnyLetter]);
+    assert_eq!(tokenize("(\\)\\|)*").unwrap(), vec![Group(vec![Letter(')'), Letter('|')]), Star]);
+    assert_eq!(tokenize("\\!a!b").unwrap(), vec![Letter('!'), Letter('a'), NegatedCharSet(vec!['b'])]);
+    assert!(tokenize("\\a").is_err());
+}
+
+#[test]
+fn test_unbalanced_parens() {
+    use Token::*;
+    assert_eq!(tokenize("a)b"), Err("Unmatched `)` at position 1".to_string()));
+    assert_eq!(tokenize("(a"), Err("Unclosed `(` starting at position 0".to_string()));
+    assert_eq!(tokenize("b((a)"), Err("Unclosed `(` starting at position 1".to_string()));
+    assert_eq!(tokenize("a(b))"), Err("Unmatched `)` at position 4".to_string()));
+    assert_eq!(tokenize("((a))"), Ok(vec![Group(vec![Group(vec![Letter('a')])])]));
 }
 
-/// Convert a parenthesized substring into a group token
-fn group(substring: &str) -> Token {
-    Token::Group(tokenize(substring))
+#[test]
+fn test_ranges() {
+    let symbols = ['a', 'c', 'd', 'e', 'f', 'x', 'y'];
+    assert_eq!(ranges(&symbols), vec!["a", "c-f", "x", "y"]);
+    assert_eq!(ranges(&('0'..='9').collect::<Vec<char>>()), vec!["0-9"]);
+    assert!(ranges(&[]).is_empty());
 }
@@ -0,0 +1,445 @@
+//! CSIS-616 - Regex Parser and Evaluator
+//! 
+//! Anthony Morrell
+//! 
+//! Converts regular expressions into finite automata. A regex is tokenized by
+//! `lexer`, parsed into a syntax tree by `parser`, and built into an NFA and
+//! then a DFA by `automata`.
+//! 
+//! ```
+//! let dfa = csis_regex::regex_to_dfa("ab*").unwrap();
+//! assert!(dfa.accepts("abbb"));
+//! assert!(!dfa.accepts("ba"));
+//! ```
+
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod lexer;
+pub mod parser;
+pub mod automata;
+pub mod multimap;
+
+use std::fmt;
+
+/// Something wrong with a regex, found while converting it to an automaton
+#[derive(Debug, PartialEq)]
+pub enum RegexError {
+    Lex(String),    // e.g. an unrecognized character or unbalanced parentheses
+    Parse(String)   // e.g. an operator with nothing to apply it to
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegexError::Lex(message) | RegexError::Parse(message) => write!(f, "{}", message)
+        }
+    }
+}
+
+impl std::error::Error for RegexError {}
+
+/// Convert a regex into a DFA
+pub fn regex_to_dfa(regex: &str) -> Result<automata::DFA, RegexError> {
+    Ok(*automata::nfa_to_dfa(&regex_to_nfa(regex)?))
+}
+
+/// Get the minimal DFA for a regex, with its states numbered in a canonical
+/// order, so two regexes for the same language give identical machines
+pub fn regex_to_minimal_dfa(regex: &str) -> Result<automata::DFA, RegexError> {
+    Ok(regex_to_dfa(regex)?.minimize())
+}
+
+/// Convert a regex into an NFA
+pub fn regex_to_nfa(regex: &str) -> Result<automata::NFA, RegexError> {
+    Ok(*automata::ast_to_nfa(&regex_to_ast(regex)?))
+}
+
+/// Lex and parse a regex into an abstract syntax tree
+pub(crate) fn regex_to_ast(regex: &str) -> Result<parser::AST, RegexError> {
+    let tokens = lexer::tokenize(regex).map_err(RegexError::Lex)?;
+    let mut tree = parser::tree();
+    parser::parse(&tokens, &mut tree).map_err(RegexError::Parse)?;
+    Ok(tree)
+}
+
+/// Find the subexpressions of a regex that can be removed without changing
+/// its language, along with the simpler regex left after removing each one
+/// Larger subexpressions are tried first, and pieces of a subexpression that
+/// was already reported (or removals that leave the same regex as an earlier
+/// one) aren't reported again.
+pub fn redundant_subexpressions(regex: &str) -> Result<Vec<(String, String)>, RegexError> {
+    let tree = regex_to_ast(regex)?;
+    let original = automata::nfa_to_dfa(&automata::ast_to_nfa(&tree));
+
+    let mut redundant: Vec<(parser::NodeId, String)> = vec![];
+    for id in (0..tree.root_id()).rev() {
+        // removing this subexpression has to leave something behind
+        let simplified = match tree.to_regex(tree.root_id(), Some(id)) {
+            Some(simplified) => simplified,
+            None => continue
+        };
+        if redundant.iter().any(|(other_id, other)| tree.in_subtree(id, *other_id) || *other == simplified) {
+            continue;
+        }
+        if original.equivalent(&regex_to_dfa(&simplified)?) {
+            redundant.push((id, simplified));
+        }
+    }
+    Ok(redundant.into_iter()
+            .map(|(id, simplified)| (tree.to_regex(id, None).unwrap(), simplified))
+            .collect())
+}
+
+/// Which of two regexes accepts the empty string, if they disagree
+#[derive(Debug, PartialEq)]
+pub enum EmptyStringDiff {
+    Same,       // both or neither accept ""
+    OnlyFirst,
+    OnlySecond
+}
+
+/// Check whether two regexes differ on the empty string, e.g. `a*` and `a+`
+/// This says nothing about any other strings the regexes might disagree on.
+pub fn regex_empty_string_diff(a: &str, b: &str) -> Result<EmptyStringDiff, RegexError> {
+    Ok(match (regex_to_dfa(a)?.accepts_empty(), regex_to_dfa(b)?.accepts_empty()) {
+        (true, false) => EmptyStringDiff::OnlyFirst,
+        (false, true) => EmptyStringDiff::OnlySecond,
+        _ => EmptyStringDiff::Same
+    })
+}
+
+mod graphviz {
+    use crate::automata::StateId;
+    use automata_core::graphviz::Diagram;
+    use std::collections::HashSet;
+    use std::fmt::Display;
+
+    pub use automata_core::graphviz::generate_clusters;
+
+    pub type Edge = (StateId, StateId, char);
+
+    /// Edges can be labeled with anything printable, e.g. a whole character class
+    pub fn generate<L: Display>(start: StateId, end: &HashSet<StateId>, edges: &[(StateId, StateId, L)]) -> String {
+        automata_core::graphviz::generate(&diagram(start, end), convert(edges))
+    }
+
+    /// Like `generate()`, but draws the given nodes dashed (and draws them even
+    /// if they have no edges)
+    pub fn generate_dashed<L: Display>(start: StateId, end: &HashSet<StateId>, dashed: &[StateId], edges: &[(StateId, StateId, L)]) -> String {
+        let diagram = Diagram {
+            dashed: dashed.iter().map(StateId::to_string).collect(),
+            ..diagram(start, end)
+        };
+        automata_core::graphviz::generate(&diagram, convert(edges))
+    }
+
+    /// Wrap a machine in a labeled cluster so several can share one graph
+    pub fn cluster<L: Display>(index: usize, label: &str, start: StateId, end: &HashSet<StateId>, edges: &[(StateId, StateId, L)]) -> String {
+        automata_core::graphviz::cluster(index, label, &diagram(start, end), convert(edges))
+    }
+
+    /// Accept states are sorted so the output is stable
+    fn diagram(start: StateId, end: &HashSet<StateId>) -> Diagram {
+        let mut end = end.iter().copied().collect::<Vec<StateId>>();
+        end.sort_unstable();
+        Diagram::new(start, end.iter().map(StateId::to_string).collect())
+    }
+
+    /// Edges between the same states are merged with their labels in order,
+    /// and runs of characters are shortened, e.g. `0-9` or `a,c-f`
+    fn convert<L: Display>(edges: &[(StateId, StateId, L)]) -> Vec<automata_core::graphviz::Edge> {
+        let mut edges = edges.iter()
+                .map(|(from, to, label)| (*from, *to, label.to_string()))
+                .collect::<Vec<(StateId, StateId, String)>>();
+        edges.sort();
+
+        let mut merged: Vec<(StateId, StateId, Vec<String>)> = vec![];
+        for (from, to, label) in edges {
+            match merged.last_mut() {
+                Some((last_from, last_to, labels)) if (*last_from, *last_to) == (from, to) => labels.push(label),
+                _ => merged.push((from, to, vec![label]))
+            }
+        }
+        merged.into_iter()
+                .map(|(from, to, labels)| {
+                    let label = match labels.iter().map(|label| single_char(label)).collect::<Option<Vec<char>>>() {
+                        Some(symbols) => crate::lexer::ranges(&symbols).join(","),
+                        None => labels.join(",")
+                    };
+                    automata_core::graphviz::Edge::labeled(from, to, label)
+                })
+                .collect()
+    }
+
+    fn single_char(label: &str) -> Option<char> {
+        let mut chars = label.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    }
+}
+
+mod mermaid {
+    use crate::automata::StateId;
+    use std::collections::HashSet;
+    use std::fmt::Display;
+
+    /// Get a Mermaid state diagram with the same information as `graphviz::generate()`
+    /// Accept states and edges are sorted so the output is stable.
+    pub fn generate<L: Display + Ord>(start: StateId, end: &HashSet<StateId>, edges: &[(StateId, StateId, L)]) -> String {
+        let mut edges = edges.iter().collect::<Vec<_>>();
+        edges.sort();
+        let mut end = end.iter().collect::<Vec<_>>();
+        end.sort();
+        format!(
+            "stateDiagram-v2\n    [*] --> q{}\n{}{}",
+            start,
+            edges.iter()
+                .map(|(from, to, label)| format!("    q{} --> q{}: {}\n", from, to, label))
+                .collect::<String>(),
+            end.iter()
+                .map(|s| format!("    q{} --> [*]\n", s))
+                .collect::<String>()
+        )
+    }
+}
+
+#[test]
+fn test() {
+    let dfa = regex_to_dfa("abab*").unwrap();
+    assert!(dfa.accepts("aba"));
+    assert!(dfa.accepts("abab"));
+    assert!(dfa.accepts("ababb"));
+
+    assert!(!dfa.accepts("ab"));
+    assert!(!dfa.accepts(""));
+    assert!(!dfa.accepts("abaa"));
+
+    let dfa = regex_to_dfa("ab?c").unwrap();
+    assert!(dfa.accepts("ac"));
+    assert!(dfa.accepts("abc"));
+    assert!(!dfa.accepts("abbc"));
+    assert!(!dfa.accepts("ab"));
+
+    // optional at the very end still leaves an accept state
+    let dfa = regex_to_dfa("ba?").unwrap();
+    assert!(dfa.accepts("b"));
+    assert!(dfa.accepts("ba"));
+    assert!(!dfa.accepts("baa"));
+    assert!(regex_to_dfa("a?").unwrap().accepts(""));
+
+    let dfa = regex_to_dfa("(ab)?(c|d?)e?").unwrap();
+    for accepted in &["", "ab", "c", "d", "e", "abce", "abde", "ce"] {
+        assert!(dfa.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["a", "cd", "abab", "ee"] {
+        assert!(!dfa.accepts(rejected), "should reject {:?}", rejected);
+    }
+    let dfa = regex_to_dfa("(a?b)+").unwrap();
+    assert!(dfa.accepts("bab"));
+    assert!(dfa.accepts("abb"));
+    assert!(!dfa.accepts("aab"));
+
+    // a star at the start of an optional body can't be left halfway
+    let dfa = regex_to_dfa("(a*b)?").unwrap();
+    for accepted in &["", "b", "aab"] {
+        assert!(dfa.accepts(accepted), "should accept {:?}", accepted);
+    }
+    assert!(!dfa.accepts("a"));
+    let dfa = regex_to_dfa("(b*a)?").unwrap();
+    assert!(dfa.accepts("bba"));
+    assert!(!dfa.accepts("b"));
+    assert!(!dfa.accepts("bb"));
+    let dfa = regex_to_dfa("x(b*a)?").unwrap();
+    assert!(dfa.accepts("x"));
+    assert!(dfa.accepts("xba"));
+    assert!(!dfa.accepts("xb"));
+}
+
+#[test]
+fn test_not_letter() {
+    let dfa = regex_to_dfa("!a*b").unwrap();
+    assert!(dfa.accepts("xyzb"));
+    assert!(dfa.accepts("b"));
+    assert!(dfa.accepts(" 9b"));
+    assert!(!dfa.accepts("ab"));
+    assert!(!dfa.accepts("xab"));
+
+    let dfa = regex_to_dfa("!a+").unwrap();
+    assert!(dfa.accepts("x"));
+    assert!(dfa.accepts("xyz"));
+    assert!(!dfa.accepts(""));
+    assert!(!dfa.accepts("xa"));
+
+    let dfa = regex_to_dfa("x!ay").unwrap();
+    assert!(dfa.accepts("xby"));
+    assert!(dfa.accepts("x y"));
+    assert!(!dfa.accepts("xay"));
+    assert!(!dfa.accepts("xy"));
+}
+
+#[test]
+fn test_repeat_range() {
+    let dfa = regex_to_dfa("a{2,3}").unwrap();
+    assert!(dfa.accepts("aa"));
+    assert!(dfa.accepts("aaa"));
+    assert!(!dfa.accepts("a"));
+    assert!(!dfa.accepts("aaaa"));
+
+    let dfa = regex_to_dfa("(ab){2}c").unwrap();
+    assert!(dfa.accepts("ababc"));
+    assert!(!dfa.accepts("abc"));
+    assert!(!dfa.accepts("abababc"));
+
+    let dfa = regex_to_dfa("ba{2,}").unwrap();
+    assert!(dfa.accepts("baa"));
+    assert!(dfa.accepts("baaaaa"));
+    assert!(!dfa.accepts("ba"));
+
+    let dfa = regex_to_dfa("a{0,2}b|c{0}").unwrap();
+    for accepted in &["", "b", "ab", "aab"] {
+        assert!(dfa.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["aaab", "c", "a"] {
+        assert!(!dfa.accepts(rejected), "should reject {:?}", rejected);
+    }
+
+    // each copy of a starred body starts fresh
+    let dfa = regex_to_dfa("(a*b){1,2}").unwrap();
+    for accepted in &["b", "ab", "bb", "abaab"] {
+        assert!(dfa.accepts(accepted), "should accept {:?}", accepted);
+    }
+    for rejected in &["ba", "aba", "a"] {
+        assert!(!dfa.accepts(rejected), "should reject {:?}", rejected);
+    }
+    let dfa = regex_to_dfa("(a*b){0,1}").unwrap();
+    assert!(dfa.accepts(""));
+    assert!(dfa.accepts("aab"));
+    assert!(!dfa.accepts("a"));
+    let dfa = regex_to_dfa("(a*b)*").unwrap();
+    assert!(dfa.accepts("abb"));
+    assert!(!dfa.accepts("a"));
+    assert!(!dfa.accepts("ba"));
+
+    // {0} inside an alternation doesn't turn into a loop
+    let dfa = regex_to_dfa("(x{0}|a)").unwrap();
+    assert!(dfa.accepts(""));
+    assert!(dfa.accepts("a"));
+    assert!(!dfa.accepts("aa"));
+}
+
+#[test]
+fn test_char_set() {
+    let dfa = regex_to_dfa("[a-c]+").unwrap();
+    assert!(dfa.accepts("a"));
+    assert!(dfa.accepts("cab"));
+    assert!(!dfa.accepts(""));
+    assert!(!dfa.accepts("abd"));
+
+    let dfa = regex_to_dfa("x[a-a0-1]").unwrap();
+    assert!(dfa.accepts("xa"));
+    assert!(dfa.accepts("x1"));
+    assert!(!dfa.accepts("xb"));
+    assert!(!dfa.accepts("x2"));
+
+    assert_eq!(redundant_subexpressions("[a-dz]|b").unwrap(), vec![("b".to_string(), "[a-dz]".to_string())]);
+}
+
+#[test]
+fn test_negated_char_set() {
+    let dfa = regex_to_dfa("[^a]b|c").unwrap();
+    assert!(dfa.accepts("cb"));
+    assert!(dfa.accepts("bb"));
+    assert!(!dfa.accepts("ab"));
+
+    // the class isn't limited to characters the regex mentions
+    let dfa = regex_to_dfa("[^a]b").unwrap();
+    assert!(dfa.accepts("cb"));
+    assert!(dfa.accepts(" b"));
+    assert!(!dfa.accepts("ab"));
+
+    let dfa = regex_to_dfa("([^0-1]x)+|\\d").unwrap();
+    assert!(dfa.accepts("2x9x"));
+    assert!(dfa.accepts("xx"));
+    assert!(dfa.accepts("yx"));
+    assert!(!dfa.accepts("1x"));
+
+    // but it does pick up escaped metacharacters the regex mentions
+    let dfa = regex_to_dfa("[^a]+\\*").unwrap();
+    assert!(dfa.accepts("**"));
+    assert!(dfa.accepts("xy*"));
+    assert!(!dfa.accepts("a*"));
+
+    // negation under quantifiers and concatenation
+    let dfa = regex_to_dfa("[^a]*b").unwrap();
+    assert!(dfa.accepts("xyzb"));
+    assert!(dfa.accepts("b"));
+    assert!(!dfa.accepts("ab"));
+    assert!(!dfa.accepts("xazb"));
+
+    let dfa = regex_to_dfa("[^a]+").unwrap();
+    assert!(dfa.accepts("xyz"));
+    assert!(dfa.accepts(" "));
+    assert!(!dfa.accepts(""));
+    assert!(!dfa.accepts("xa"));
+
+    let dfa = regex_to_dfa("x[^a]y").unwrap();
+    assert!(dfa.accepts("xzy"));
+    assert!(dfa.accepts("xxy"));
+    assert!(!dfa.accepts("xay"));
+    assert!(!dfa.accepts("xy"));
+}
+
+#[test]
+fn test_escaped_metachars() {
+    let dfa = regex_to_dfa("a\\*b").unwrap();
+    assert!(dfa.accepts("a*b"));
+    assert!(!dfa.accepts("aab"));
+    assert!(!dfa.accepts("ab"));
+
+    let dfa = regex_to_dfa("a\\+b").unwrap();
+    assert!(dfa.accepts("a+b"));
+    assert!(!dfa.accepts("aab"));
+
+    let dfa = regex_to_dfa("(\\(\\)|\\\\)+\\d").unwrap();
+    assert!(dfa.accepts("()\\()4"));
+    assert!(!dfa.accepts("()"));
+
+    assert_eq!(redundant_subexpressions("\\*|\\**").unwrap(), vec![("\\*".to_string(), "\\**".to_string())]);
+}
+
+#[test]
+fn test_regex_empty_string_diff() {
+    assert_eq!(regex_empty_string_diff("a*", "a+").unwrap(), EmptyStringDiff::OnlyFirst);
+    assert_eq!(regex_empty_string_diff("aa*", "a*").unwrap(), EmptyStringDiff::OnlySecond);
+    assert_eq!(regex_empty_string_diff("a*", "b*").unwrap(), EmptyStringDiff::Same);
+    assert_eq!(regex_empty_string_diff("a", "ab").unwrap(), EmptyStringDiff::Same);
+}
+
+#[test]
+fn test_redundant_subexpressions() {
+    assert_eq!(redundant_subexpressions("a*a*").unwrap(), vec![("a*".to_string(), "a*".to_string())]);
+    assert_eq!(redundant_subexpressions("(a|ab)*b*|b").unwrap(), vec![("b".to_string(), "(a|ab)*b*".to_string())]);
+    assert_eq!(redundant_subexpressions("(a|b|ab)*").unwrap(), vec![("ab".to_string(), "(a|b)*".to_string())]);
+    assert!(redundant_subexpressions("ab*").unwrap().is_empty());
+}
+
+#[test]
+fn test_regex_to_minimal_dfa() {
+    assert_eq!(regex_to_minimal_dfa("a|a").unwrap().num_states(), 2);
+    assert_eq!(regex_to_minimal_dfa("a|a").unwrap().to_mermaid(), regex_to_minimal_dfa("a").unwrap().to_mermaid());
+    assert_eq!(regex_to_minimal_dfa("(a|b)*").unwrap().num_states(), 1);
+    assert_eq!(regex_to_minimal_dfa("(a|b)*").unwrap().to_mermaid(), regex_to_minimal_dfa("(a*b*)*").unwrap().to_mermaid());
+}
+
+#[test]
+fn test_regex_error() {
+    assert_eq!(regex_to_dfa("a)b").unwrap_err(), RegexError::Lex("Unmatched `)` at position 1".to_string()));
+    assert!(matches!(regex_to_dfa("a|*"), Err(RegexError::Parse(_))));
+    assert!(matches!(regex_to_dfa(""), Err(RegexError::Parse(_))));
+    assert!(redundant_subexpressions("(a").is_err());
+}
+
+#[test]
+fn test_graph_ranges() {
+    assert!(regex_to_dfa("\\d").unwrap().to_graph().contains("    0 -> 1 [label=\"0-9\"];\n"));
+    assert!(regex_to_dfa("[ac-f]").unwrap().to_graph().contains("    0 -> 1 [label=\"a,c-f\"];\n"));
+}
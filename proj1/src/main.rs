@@ -5,52 +5,117 @@
 //! # Usage
 //! 
 //!    ```
-//!     ./regex string
+//!     ./regex string [--test cases] [--analyze] [--output file] [--format graphviz|mermaid]
 //!     ```
 //! 
 //!    where: `string` is a regular expression
+//!           `cases` is a list of strings and expected results, e.g.
+//!           `a:1,ab:1,b:0` (1 = accept, 0 = reject)
+//!           `--analyze` lists subexpressions that don't change the language
+//!           `--output` writes the diagram to `file` instead of printing it
+//!           `--format` picks GraphViz (the default) or a Mermaid state diagram
 //! 
 //! # Output
 //! 
+//! With no `string`, the regex is read from the first line of `stdin` if
+//! it's piped in, and the rest of `stdin` is tested against it.
+//! 
 //! Output is sent to `stdout` and `stderr`. Build and run using:
 //! 
 //! ```
 //! cargo run "ab*"
 //! ```
 
-mod lexer;
-mod parser;
-mod automata;
-mod multimap;
+use csis_regex::{automata::{explosion_warning, nfa_to_dfa, DFA}, redundant_subexpressions, regex_to_nfa, RegexError};
 
 /// If the regex contains contiguous spaces, you must wrap it in quotes, e.g. "a  b"
 fn main() {
-    use std::io::BufRead;
+    use std::io::{BufRead, IsTerminal};
 
-    // get command line args as a string
-    let args = std::env::args()
-            .skip(1)
-            .collect::<Vec<String>>()
-            .join(" ");
+    // pull out the flags and treat the rest of the args as the regex
+    let command = command();
+    let matches = command.parse();
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let regex = match matches.arg("regex") {
+        Some(regex) => Some(regex.to_string()),
+        // take it from the first line of stdin if that's piped in
+        None if !std::io::stdin().is_terminal() => read_regex(&mut input).unwrap_or_else(|error| {
+            eprintln!("Error reading from stdin: {}", error);
+            std::process::exit(1);
+        }),
+        None => None
+    };
+    let args = match &regex {
+        Some(regex) => regex.as_str(),
+        None => {
+            // print the usage if there's no args
+            println!("{}", command.usage());
+            std::process::exit(0);
+        }
+    };
 
-    // print the usage if there's no args
-    if args.is_empty() {
-        println!("Usage: ./regex <regex>");
+    // report redundant pieces of the regex instead of reading from stdin
+    if matches.flag("analyze") {
+        let redundant = redundant_subexpressions(args).unwrap_or_else(|error| exit_with_error(&error));
+        for (subexpression, simplified) in &redundant {
+            println!("`{}` is redundant: `{}` matches the same strings", subexpression, simplified);
+        }
+        if redundant.is_empty() {
+            println!("No redundant subexpressions");
+        }
         std::process::exit(0);
     }
 
-    // convert the regex to a dfa
-    let dfa = regex_to_dfa(&args);
+    // convert the regex to a dfa, warning first if that might take a while
+    let nfa = regex_to_nfa(args).unwrap_or_else(|error| exit_with_error(&error));
+    if let Some(warning) = explosion_warning(&nfa) {
+        eprintln!("Warning: {}", warning);
+    }
+    let dfa = *nfa_to_dfa(&nfa);
 
-    // print the graphviz definition
-    println!("---[ DFA Graph ]----------------");
-    println!("{}", dfa.to_graph());
-    println!("--------------------------------");
+    // check the given test cases instead of reading from stdin
+    if let Some(spec) = matches.value("test") {
+        let cases = parse_test_spec(spec).unwrap_or_else(|error| {
+            eprintln!("Invalid test cases: {}", error);
+            std::process::exit(1);
+        });
+        let failures = failed_cases(&dfa, &cases);
+        for (string, expected) in &failures {
+            let outcome = |accept| if accept { "accept" } else { "reject" };
+            println!("FAIL `{}`: expected {}, got {}", string, outcome(*expected), outcome(!expected));
+        }
+        println!("{} passed, {} failed", cases.len() - failures.len(), failures.len());
+        std::process::exit(if failures.is_empty() { 0 } else { 1 });
+    }
+
+    // save or print the graphviz (or mermaid) definition
+    let diagram = match matches.value("format") {
+        Some("mermaid") => dfa.to_mermaid(),
+        _ => dfa.to_graph()
+    };
+    if let Some(path) = matches.value("output") {
+        cli::write_file(path, &diagram).unwrap_or_else(|msg| {
+            eprintln!("{}", msg);
+            std::process::exit(1);
+        });
+    } else {
+        println!("---[ DFA Graph ]----------------");
+        println!("{}", diagram);
+        println!("--------------------------------");
+    }
 
     // run user given strings through dfa
     println!("Enter strings to test them:");
-    let stdin = std::io::stdin();
-    for line in stdin.lock().lines() {
+    for line in input.lines() {
+        if let Ok(line) = &line {
+            for (state, letter, next_state) in dfa.trace(line) {
+                match next_state {
+                    Some(next_state) => eprintln!("  \u{3B4}(q{}, {}) \u{2192} q{}", state, letter, next_state),
+                    None => eprintln!("  \u{3B4}(q{}, {}) is undefined", state, letter)
+                }
+            }
+        }
         match line {
             Ok(line) if dfa.accepts(&line) => eprintln!("Accept {}", line),
             Ok(line) => eprintln!("Reject {}", line),
@@ -62,52 +127,92 @@ fn main() {
     }
 }
 
-fn regex_to_dfa(regex: &str) -> Box<automata::DFA> {
-    // lex and parse
-    let tokens = lexer::tokenize(regex);
-    let mut tree = parser::tree();
-    parser::parse(&tokens, &mut tree);
-
-    // make nfa, then dfa
-    let nfa = automata::ast_to_nfa(&tree);
-    automata::nfa_to_dfa(&nfa)
+/// Describe the command line arguments
+fn command() -> cli::Command {
+    cli::Command::new("regex", "Convert a regular expression to a DFA and run strings through it")
+            .option("test", "string:0|1,...", "Check these cases instead of reading stdin (1 = accept, 0 = reject)")
+            .flag("analyze", "List subexpressions that don't change the language")
+            .option("output", "file", "Write only the diagram to this file")
+            .choice("format", "graphviz|mermaid", "Diagram format (default: graphviz)")
+            .rest("regex", "The regular expression (quote it if it has contiguous spaces)")
 }
 
-mod graphviz {
-    use crate::automata::StateId;
-    use std::collections::HashSet;
-
-    pub type Edge = (StateId, StateId, char);
-
-    pub fn generate(start: StateId, end: &HashSet<StateId>, edges: &Vec<Edge>) -> String {
-        format!(
-            "digraph {{\n\
-                rankdir=LR;\n\
-                node [shape=point]; start;\n\
-                node [shape=doublecircle]; {end_nodes}\n\
-                node [shape=circle];\n\
-                start -> {start_node};\n\
-                {edges}\
-            }}",
-            start_node=start,
-            end_nodes=end.iter()
-                .map(|&s| s.to_string() + "; ")
-                .collect::<String>(),
-            edges=edges.iter()
-                .map(|(from, to, label)| format!("{} -> {} [label=\"{}\"];\n", from, to, label))
-                .collect::<String>()
-        )
+/// Read a regex from the first line of `reader`, or None if it's empty
+/// The rest of `reader` is left for the strings to test.
+fn read_regex(reader: &mut impl std::io::BufRead) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
     }
+    let regex = line.trim_end_matches(&['\n', '\r'][..]);
+    Ok(Some(regex.to_string()).filter(|regex| !regex.is_empty()))
+}
+
+/// Print a problem with the regex and quit
+fn exit_with_error(error: &RegexError) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
+}
+
+/// Parse test cases of the form `string:expected,...`, where expected is
+/// 1 (accept) or 0 (reject), e.g. `a:1,ab:1,b:0`
+fn parse_test_spec(spec: &str) -> Result<Vec<(String, bool)>, String> {
+    spec.split(',')
+            .map(|case| match case.rsplit_once(':') {
+                Some((string, "1")) => Ok((string.to_string(), true)),
+                Some((string, "0")) => Ok((string.to_string(), false)),
+                _ => Err(format!("`{}` should look like `string:1` or `string:0`", case))
+            })
+            .collect()
+}
+
+/// Get the test cases the DFA gets wrong
+fn failed_cases<'a>(dfa: &DFA, cases: &'a [(String, bool)]) -> Vec<&'a (String, bool)> {
+    cases.iter()
+            .filter(|(string, expected)| dfa.accepts(string) != *expected)
+            .collect()
+}
+
+#[test]
+fn test_test_spec() {
+    let cases = parse_test_spec("a:1,ab:1,b:0,:0,abb:0").unwrap();
+    assert_eq!(cases[3], (String::new(), false));
+
+    let dfa = csis_regex::regex_to_dfa("ab*").unwrap();
+    let failures = failed_cases(&dfa, &cases);
+    assert_eq!(failures, vec![&("abb".to_string(), false)]);
+
+    assert!(parse_test_spec("a:1,b").is_err());
+    assert!(parse_test_spec("a:yes").is_err());
+}
+
+#[test]
+fn test_output() {
+    let dfa = csis_regex::regex_to_dfa("ab*").unwrap();
+    let path = std::env::temp_dir().join("regex_test_output.dot");
+    let path = path.to_str().unwrap();
+    cli::write_file(path, &dfa.to_graph()).unwrap();
+    assert_eq!(std::fs::read_to_string(path).unwrap(), dfa.to_graph());
+    std::fs::remove_file(path).unwrap();
 }
 
 #[test]
-fn test() {
-    let dfa = regex_to_dfa("abab*");
-    assert!(dfa.accepts("aba"));
-    assert!(dfa.accepts("abab"));
-    assert!(dfa.accepts("ababb"));
-
-    assert!(!dfa.accepts("ab"));
-    assert!(!dfa.accepts(""));
-    assert!(!dfa.accepts("abaa"));
+fn test_read_regex() {
+    use std::io::BufRead;
+
+    let mut input = std::io::Cursor::new("ab*\r\nab\nabbb\n");
+    let regex = read_regex(&mut input).unwrap().unwrap();
+    assert_eq!(regex, "ab*");
+
+    // the remaining lines are the strings to test
+    let dfa = csis_regex::regex_to_dfa(&regex).unwrap();
+    let lines = input.lines().map(Result::unwrap).collect::<Vec<String>>();
+    assert_eq!(lines, vec!["ab", "abbb"]);
+    assert!(lines.iter().all(|line| dfa.accepts(line)));
+
+    // spaces are kept as-is, since there's no shell to strip them
+    let regex = read_regex(&mut std::io::Cursor::new("a  b\n")).unwrap();
+    assert_eq!(regex.as_deref(), Some("a  b"));
+    assert_eq!(read_regex(&mut std::io::Cursor::new("")).unwrap(), None);
+    assert_eq!(read_regex(&mut std::io::Cursor::new("\nab\n")).unwrap(), None);
 }
@@ -14,11 +14,22 @@ pub trait MultiMapMethods<K, V> {
     /// Get the set of values associated with a key
     fn get_multi(&self, key: K) -> HashSet<V>;
 
+    /// Borrow the set of values associated with a key, if there are any
+    /// Cheaper than `get_multi()` when the set only needs to be read.
+    fn get_multi_ref(&self, key: K) -> Option<&HashSet<V>>;
+
     /// Add a mapping from a key to a value
     fn add_multi(&mut self, key: K, value: V);
 
     /// Add a set of mappings from a key to a value
     fn add_all_multi(&mut self, key: K, values: &HashSet<V>);
+
+    /// Remove a single mapping, returning whether it was there
+    /// A key left with no values is removed entirely.
+    fn remove_multi(&mut self, key: K, value: &V) -> bool;
+
+    /// Check whether a key maps to a value
+    fn contains_multi(&self, key: K, value: &V) -> bool;
 }
 
 impl<K, V> MultiMapMethods<K, V> for MultiMap<K, V> where
@@ -32,6 +43,10 @@ impl<K, V> MultiMapMethods<K, V> for MultiMap<K, V> where
         }
     }
 
+    fn get_multi_ref(&self, key: K) -> Option<&HashSet<V>> {
+        self.get(&key)
+    }
+
     fn add_multi(&mut self, key: K, value: V) {
         self.entry(key).or_default().insert(value);
     }
@@ -40,6 +55,22 @@ impl<K, V> MultiMapMethods<K, V> for MultiMap<K, V> where
         let set = self.entry(key).or_default();
         values.iter().for_each(|v| { set.insert(v.clone()); });
     }
+
+    fn remove_multi(&mut self, key: K, value: &V) -> bool {
+        let set = match self.get_mut(&key) {
+            Some(set) => set,
+            None => return false
+        };
+        let removed = set.remove(value);
+        if set.is_empty() {
+            self.remove(&key);
+        }
+        removed
+    }
+
+    fn contains_multi(&self, key: K, value: &V) -> bool {
+        self.get(&key).is_some_and(|set| set.contains(value))
+    }
 }
 
 /// Compute the union of a list of multimaps
@@ -56,3 +87,35 @@ pub fn union_multi<K, V>(maps: &[&MultiMap<K, V>]) -> MultiMap<K, V> where
     }
     union
 }
+
+#[test]
+fn test_remove_multi() {
+    let mut map: MultiMap<char, u16> = HashMap::new();
+    map.add_multi('a', 1);
+    map.add_multi('a', 2);
+    assert!(map.contains_multi('a', &1));
+    assert!(!map.contains_multi('a', &3));
+    assert!(!map.contains_multi('b', &1));
+
+    assert!(map.remove_multi('a', &1));
+    assert!(!map.remove_multi('a', &1));
+    assert!(!map.contains_multi('a', &1));
+    assert!(map.contains_key(&'a'));
+
+    // removing the last value removes the key too
+    assert!(map.remove_multi('a', &2));
+    assert!(!map.contains_key(&'a'));
+    assert!(!map.remove_multi('b', &1));
+}
+
+#[test]
+fn test_get_multi_ref() {
+    let mut map: MultiMap<char, u16> = HashMap::new();
+    map.add_multi('a', 1);
+    map.add_multi('a', 2);
+    map.add_multi('b', 3);
+    for key in "abc".chars() {
+        assert_eq!(map.get_multi_ref(key).cloned().unwrap_or_default(), map.get_multi(key));
+    }
+    assert_eq!(map.get_multi_ref('c'), None);
+}
@@ -1,4 +1,5 @@
 use crate::lexer::Token;
+use std::collections::HashSet;
 
 pub type NodeId = usize;
 
@@ -17,13 +18,22 @@ pub enum Node {
     And(NodeId, NodeId),
     Or(NodeId, NodeId),
     RepeatStar(NodeId),
-    RepeatPlus(NodeId)
+    RepeatPlus(NodeId),
+    RepeatOptional(NodeId),
+    RepeatRange(NodeId, usize, Option<usize>)
 }
 
-/// A character class, either "all letters" or "all digits"
-#[derive(Clone, Copy, Debug)]
+/// A character class, either "all letters", "all digits", an explicit set of
+/// characters from a bracket expression like `[a-f0-3xyz]`, or a negated
+/// bracket expression like `[^0-9]` (or `!x`)
+/// A negated set is taken against the regex character set (a-z, 0-9, space)
+/// plus anything else the regex mentions, like an escaped `*`. For example,
+/// `[^a]*b` matches `xyzb`, and `[^a]\*` matches `**`.
+#[derive(Clone, Debug)]
 pub enum CharClass {
-    AllLetter, AllDigit
+    AllLetter, AllDigit,
+    Set(Vec<char>),
+    NotSet(Vec<char>)
 }
 
 /// Creates an empty abstract syntax tree
@@ -32,33 +42,45 @@ pub fn tree() -> AST {
 }
 
 /// Parses a sequence of tokens into a tree from the bottom up
-pub fn parse(tokens: &[Token], tree: &mut AST) -> NodeId {
+/// Fails if the tokens don't form a valid regex, e.g. an operator with
+/// nothing to apply it to.
+pub fn parse(tokens: &[Token], tree: &mut AST) -> Result<NodeId, String> {
     use crate::lexer;
     use Node::*;
     
     let new_node =
         // find alternations (|)
         if let Some(index) = lexer::find(tokens, Token::Union) {
-            let left = parse(&tokens[..index], tree);
-            let right = parse(&tokens[index+1..], tree);
+            let left = parse(&tokens[..index], tree)?;
+            let right = parse(&tokens[index+1..], tree)?;
             Or(left, right)
 
         // find concatenations
         } else if let Some((_, index)) = lexer::find_adjacent_values(tokens) {
-            let left = parse(&tokens[..index], tree);
-            let right = parse(&tokens[index..], tree);
+            let left = parse(&tokens[..index], tree)?;
+            let right = parse(&tokens[index..], tree)?;
             And(left, right)
 
         // find repetitions (*)
         } else if let Some(index) = lexer::find(tokens, Token::Star) {
-            let child = parse(&tokens[..index], tree);
+            let child = parse(&tokens[..index], tree)?;
             RepeatStar(child)
 
         // find plus repetitions (+)
         } else if let Some(index) = lexer::find(tokens, Token::Plus) {
-            let child = parse(&tokens[..index], tree);
+            let child = parse(&tokens[..index], tree)?;
             RepeatPlus(child)
 
+        // find optionals (?)
+        } else if let Some(index) = lexer::find(tokens, Token::Optional) {
+            let child = parse(&tokens[..index], tree)?;
+            RepeatOptional(child)
+
+        // find bounded repetitions ({n}, {n,}, {n,m})
+        } else if let Some((index, min, max)) = lexer::find_repeat_range(tokens) {
+            let child = parse(&tokens[..index], tree)?;
+            RepeatRange(child, min, max)
+
         // find letters and parenthesized expressions
         } else if tokens.len() == 1 {
             match tokens.first().unwrap() {
@@ -66,16 +88,17 @@ pub fn parse(tokens: &[Token], tree: &mut AST) -> NodeId {
                 Token::Group(tokens) => return parse(tokens, tree),
                 Token::AnyLetter => LeafCharClass(CharClass::AllLetter),
                 Token::AnyDigit => LeafCharClass(CharClass::AllDigit),
+                Token::CharSet(chars) => LeafCharClass(CharClass::Set(chars.clone())),
+                Token::NegatedCharSet(chars) => LeafCharClass(CharClass::NotSet(chars.clone())),
                 _ => panic!("Badly malformed regex") // shouldn't ever happen
             }
         
         // anything else must be a syntax error
         } else {
-            eprintln!("Malformed regex: {:?}", tokens);
-            std::process::exit(1);
+            return Err(format!("Malformed regex: {:?}", tokens));
         };
 
-    tree.add(new_node)
+    Ok(tree.add(new_node))
 }
 
 impl AST {
@@ -87,11 +110,173 @@ impl AST {
 
     /// Get the root node (the last element of the node list)
     pub fn root(&self) -> &Node {
-        &self.nodes.last().unwrap()
+        self.nodes.last().unwrap()
     }
 
     /// Get a particular node given its index
     pub fn get(&self, id: NodeId) -> &Node {
         &self.nodes[id]
     }
+
+    /// Get the index of the root node
+    pub fn root_id(&self) -> NodeId {
+        self.nodes.len() - 1
+    }
+
+    /// Get the GraphViz representation of this tree, with the root at the top
+    pub fn to_graph(&self) -> String {
+        use Node::*;
+        let mut nodes = String::new();
+        let mut edges = String::new();
+        let mut stack = vec![self.root_id()];
+        while let Some(id) = stack.pop() {
+            let (label, children) = match *self.get(id) {
+                Leaf(chr) => (format!("'{}'", chr).replace('\\', "\\\\"), vec![]),
+                LeafCharClass(ref class) => (class.to_regex().replace('\\', "\\\\"), vec![]),
+                And(first, second) => ("And".to_string(), vec![first, second]),
+                Or(choice1, choice2) => ("Or".to_string(), vec![choice1, choice2]),
+                RepeatStar(body) => ("RepeatStar".to_string(), vec![body]),
+                RepeatPlus(body) => ("RepeatPlus".to_string(), vec![body]),
+                RepeatOptional(body) => ("RepeatOptional".to_string(), vec![body]),
+                RepeatRange(body, min, max) => (format!("RepeatRange{}", range_suffix(min, max)), vec![body])
+            };
+            nodes.push_str(&format!("n{} [label=\"{}\"];\n", id, label));
+            for &child in &children {
+                edges.push_str(&format!("n{} -> n{};\n", id, child));
+            }
+            stack.extend(children.into_iter().rev());
+        }
+        format!("digraph {{\nnode [shape=box];\n{}{}}}", nodes, edges)
+    }
+
+    /// Get the working alphabet of this tree: every letter it mentions, either
+    /// on its own or as part of a character class
+    pub fn alphabet(&self) -> HashSet<char> {
+        let mut alphabet = HashSet::new();
+        for node in &self.nodes {
+            match node {
+                Node::Leaf(chr) => { alphabet.insert(*chr); },
+                Node::LeafCharClass(CharClass::NotSet(chars)) => alphabet.extend(chars),
+                Node::LeafCharClass(class) => alphabet.extend(class.members(&HashSet::new())),
+                _ => ()
+            }
+        }
+        alphabet
+    }
+
+    /// Check whether `id` is part of the subtree starting at `ancestor`
+    pub fn in_subtree(&self, id: NodeId, ancestor: NodeId) -> bool {
+        use Node::*;
+        id == ancestor || match *self.get(ancestor) {
+            Leaf(_) | LeafCharClass(_) => false,
+            And(first, second) | Or(first, second) => self.in_subtree(id, first) || self.in_subtree(id, second),
+            RepeatStar(body) | RepeatPlus(body) | RepeatOptional(body) | RepeatRange(body, ..) => self.in_subtree(id, body)
+        }
+    }
+
+    /// Write the subtree starting at `id` back out as a regex, leaving out the
+    /// subtree at `removed` if there is one
+    /// Leaving out one side of a concatenation or alternation leaves just the
+    /// other side, and leaving out the body of a repetition leaves out the
+    /// whole repetition. Returns None if nothing is left (the empty string).
+    pub fn to_regex(&self, id: NodeId, removed: Option<NodeId>) -> Option<String> {
+        use Node::*;
+        if Some(id) == removed {
+            return None;
+        }
+
+        // wrap a child in parentheses if it would otherwise bind too loosely
+        let child = |child: NodeId, loose: fn(&Node) -> bool| {
+            self.to_regex(child, removed).map(|regex| {
+                if loose(self.get(child)) { format!("({})", regex) } else { regex }
+            })
+        };
+        let is_or = |node: &Node| matches!(node, Or(..));
+        let is_compound = |node: &Node| !matches!(node, Leaf(_) | LeafCharClass(_));
+
+        match *self.get(id) {
+            Leaf(chr) if crate::lexer::is_metachar(chr) => Some(format!("\\{}", chr)),
+            Leaf(chr) => Some(chr.to_string()),
+            LeafCharClass(ref class) => Some(class.to_regex()),
+            And(first, second) => match (child(first, is_or), child(second, is_or)) {
+                (Some(first), Some(second)) => Some(first + &second),
+                (first, second) => first.or(second)
+            },
+            Or(choice1, choice2) => match (self.to_regex(choice1, removed), self.to_regex(choice2, removed)) {
+                (Some(choice1), Some(choice2)) => Some(format!("{}|{}", choice1, choice2)),
+                (choice1, choice2) => choice1.or(choice2)
+            },
+            RepeatStar(body) => child(body, is_compound).map(|body| body + "*"),
+            RepeatPlus(body) => child(body, is_compound).map(|body| body + "+"),
+            RepeatOptional(body) => child(body, is_compound).map(|body| body + "?"),
+            RepeatRange(body, min, max) => child(body, is_compound).map(|body| body + &range_suffix(min, max))
+        }
+    }
+}
+
+impl CharClass {
+    /// Get every character in this class in ascending order
+    /// A negated set is expanded against the regex character set plus
+    /// `alphabet`.
+    pub fn members(&self, alphabet: &HashSet<char>) -> Vec<char> {
+        match self {
+            CharClass::AllLetter => ('a'..='z').collect(),
+            CharClass::AllDigit => ('0'..='9').collect(),
+            CharClass::Set(chars) => chars.clone(),
+            CharClass::NotSet(chars) => {
+                let mut members = crate::lexer::letters()
+                        .chain(alphabet.iter().copied())
+                        .filter(|chr| !chars.contains(chr))
+                        .collect::<Vec<char>>();
+                members.sort_unstable();
+                members.dedup();
+                members
+            }
+        }
+    }
+
+    /// Write this class back out as a regex, collapsing runs of consecutive
+    /// characters in a set into ranges
+    fn to_regex(&self) -> String {
+        match self {
+            CharClass::AllLetter => "\\w".to_string(),
+            CharClass::AllDigit => "\\d".to_string(),
+            CharClass::Set(chars) => format!("[{}]", crate::lexer::ranges(chars).concat()),
+            CharClass::NotSet(chars) => format!("[^{}]", crate::lexer::ranges(chars).concat())
+        }
+    }
+}
+
+/// Write out the braces of a bounded repetition, e.g. `{2,3}`
+fn range_suffix(min: usize, max: Option<usize>) -> String {
+    match max {
+        Some(max) if max == min => format!("{{{}}}", min),
+        Some(max) => format!("{{{},{}}}", min, max),
+        None => format!("{{{},}}", min)
+    }
+}
+
+#[test]
+fn test_to_graph() {
+    let tokens = crate::lexer::tokenize("a|bc").unwrap();
+    let mut ast = tree();
+    parse(&tokens, &mut ast).unwrap();
+    assert_eq!(ast.to_graph(),
+"digraph {
+node [shape=box];
+n4 [label=\"Or\"];
+n0 [label=\"'a'\"];
+n3 [label=\"And\"];
+n1 [label=\"'b'\"];
+n2 [label=\"'c'\"];
+n4 -> n0;
+n4 -> n3;
+n3 -> n1;
+n3 -> n2;
+}");
+
+    let tokens = crate::lexer::tokenize("\\d*").unwrap();
+    let mut ast = tree();
+    parse(&tokens, &mut ast).unwrap();
+    assert!(ast.to_graph().contains("n0 [label=\"\\\\d\"];\nn1"));
 }